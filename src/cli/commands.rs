@@ -0,0 +1,294 @@
+use crate::cli::{Cli, Command, Field, Format, Passphrase};
+use crate::contracts::TuiPwSafeErrors;
+use crate::contracts::TuiPwSafeErrors::{
+    AmbiguousEntry, ClipboardUnavailable, EmptySafe, EntryNotFound, Expired, FieldNotSet,
+    PassphraseUnavailable,
+};
+use crate::model::diceware::Wordlist;
+use crate::model::record::DbRecord;
+use crate::model::secret::MASK;
+use crate::model::{format_time, now, SafeModel};
+use crate::ui::view_models::run;
+use arboard::Clipboard;
+use serde_json::{json, Map, Value};
+use std::env;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const LABEL_WIDTH: usize = 10;
+
+pub fn execute(cli: Cli) -> Result<(), TuiPwSafeErrors> {
+    let mut model = match cli.command {
+        Command::Tui => return run(SafeModel::new(&cli.safe)?, cli.config),
+        Command::Init => return run(SafeModel::create(&cli.safe)?, cli.config),
+        Command::Generate(policy) => return generate(policy.generate()?, policy.entropy()),
+        Command::GeneratePassphrase(diceware) => {
+            let wordlist = Wordlist::load(cli.config.wordlist.as_deref())?;
+            return generate(diceware.generate(&wordlist)?, diceware.entropy(&wordlist));
+        }
+        _ => SafeModel::new(&cli.safe)?,
+    };
+    model.unlock(&read_passphrase(&cli)?)?;
+    match cli.command {
+        Command::List(format, show_secrets) => list(&model, format, show_secrets),
+        Command::Show(entry, show_secrets) => show(find(&model, &entry)?, show_secrets),
+        Command::Get(entry, field) => println!("{}", value(find(&model, &entry)?, field)?),
+        Command::Copy(entry, field) => copy(
+            value(find(&model, &entry)?, field)?,
+            cli.config.clipboard_timeout,
+        )?,
+        Command::Expiring(days) => expiring(&model, days)?,
+        Command::GeneratePolicy(name) => {
+            let policy = model.named_policy(&name)?;
+            generate(policy.generate()?, policy.entropy())?
+        }
+        Command::Tui | Command::Init | Command::Generate(_) | Command::GeneratePassphrase(_) => {}
+    }
+    Ok(())
+}
+
+fn read_passphrase(cli: &Cli) -> Result<String, TuiPwSafeErrors> {
+    match &cli.passphrase {
+        Passphrase::Prompt => Ok(rpassword::prompt_password(format!(
+            "Password for {}: ",
+            cli.safe
+        ))?),
+        Passphrase::Env(var) => env::var(var)
+            .map_err(|_| PassphraseUnavailable(format!("environment variable {}", var))),
+        Passphrase::Fd(fd) => {
+            // opened by path, the descriptor stays with the caller
+            let source = || PassphraseUnavailable(format!("file descriptor {}", fd));
+            let file = File::open(format!("/dev/fd/{}", fd)).map_err(|_| source())?;
+            let mut line = String::new();
+            BufReader::new(file)
+                .read_line(&mut line)
+                .map_err(|_| source())?;
+            Ok(line.trim_end_matches(['\r', '\n']).to_string())
+        }
+    }
+}
+
+fn list(model: &SafeModel, format: Format, show_secrets: bool) {
+    let mut names = model.groups();
+    // entries without a group are at the root, listed under an empty group name first
+    if !model.by_group_name("").is_empty() {
+        names.insert(String::new());
+    }
+    let mut groups: Vec<(String, Vec<&DbRecord>)> = names
+        .into_iter()
+        .map(|g| {
+            let mut records = model.by_group_name(&g);
+            records.sort_by(|a, b| a.title().cmp(&b.title()));
+            (g, records)
+        })
+        .collect();
+    groups.sort_by(|a, b| a.0.cmp(&b.0));
+
+    match format {
+        Format::Text => {
+            for (group, records) in groups {
+                let indent = if group.is_empty() { "" } else { "  " };
+                if !group.is_empty() {
+                    println!("{}", group);
+                }
+                for record in records {
+                    let title = record.title().unwrap_or_default();
+                    match record.password().filter(|_| show_secrets) {
+                        Some(p) => println!("{}{}\t{}", indent, title, p),
+                        None => println!("{}{}", indent, title),
+                    }
+                }
+            }
+        }
+        Format::Json => {
+            let groups: Vec<Value> = groups
+                .into_iter()
+                .map(|(group, records)| {
+                    let entries: Vec<Value> = records
+                        .into_iter()
+                        .map(|r| {
+                            let mut entry = Map::new();
+                            let uuid = r.uuid().map(|u| u.to_string());
+                            let fields = [
+                                ("uuid", uuid.as_deref()),
+                                ("title", r.title()),
+                                ("username", r.username()),
+                                ("url", r.url()),
+                                ("password", r.password().filter(|_| show_secrets)),
+                            ];
+                            for (name, value) in fields {
+                                if let Some(value) = value {
+                                    entry.insert(name.to_string(), Value::from(value));
+                                }
+                            }
+                            Value::Object(entry)
+                        })
+                        .collect();
+                    json!({ "group": group, "entries": entries })
+                })
+                .collect();
+            println!("{}", Value::Array(groups));
+        }
+        Format::Tsv => {
+            let mut header = vec!["group", "title", "username", "url"];
+            if show_secrets {
+                header.push("password");
+            }
+            println!("{}", header.join("\t"));
+            for (group, records) in &groups {
+                for record in records {
+                    let mut row = vec![
+                        tsv(group),
+                        tsv(record.title().unwrap_or_default()),
+                        tsv(record.username().unwrap_or_default()),
+                        tsv(record.url().unwrap_or_default()),
+                    ];
+                    if show_secrets {
+                        row.push(tsv(record.password().unwrap_or_default()));
+                    }
+                    println!("{}", row.join("\t"));
+                }
+            }
+        }
+    }
+}
+
+// tabs and line breaks would split the row
+fn tsv(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\r', "\\r")
+        .replace('\n', "\\n")
+}
+
+fn show(record: &DbRecord, show_secrets: bool) {
+    let notes = record.note_lines().collect::<Vec<_>>().join("\n");
+    let rows = [
+        ("Title", record.title()),
+        ("Group", record.group()),
+        ("Username", record.username()),
+        (
+            "Password",
+            record
+                .password()
+                .map(|p| if show_secrets { p } else { MASK }),
+        ),
+        ("URL", record.url()),
+        ("Notes", Some(notes.as_str())),
+    ];
+    for (label, value) in rows {
+        if let Some(value) = value.filter(|v| !v.is_empty()) {
+            let indent = format!("\n{:width$}", "", width = LABEL_WIDTH);
+            let value = value.replace('\n', &indent);
+            println!(
+                "{:width$}{}",
+                format!("{}:", label),
+                value,
+                width = LABEL_WIDTH
+            );
+        }
+    }
+}
+
+/// Resolve <group>/<title>, or a title alone if it is unique in the safe.
+/// Entries without a group are `/<title>`
+fn find<'a>(model: &'a SafeModel, entry: &str) -> Result<&'a DbRecord, TuiPwSafeErrors> {
+    let matches_title = |title: &str, r: &&DbRecord| r.title().is_some_and(|t| t == title);
+    let found: Vec<&DbRecord> = if entry.contains('/') {
+        // group names may contain a slash themselves, so every split is tried
+        entry
+            .match_indices('/')
+            .flat_map(|(i, _)| {
+                let (group, title) = (&entry[..i], &entry[i + 1..]);
+                model
+                    .by_group_name(group)
+                    .into_iter()
+                    .filter(move |r| matches_title(title, r))
+            })
+            .collect()
+    } else {
+        model
+            .records()
+            .filter(|r| matches_title(entry, r))
+            .collect()
+    };
+    match found.as_slice() {
+        [] if model.is_empty() => Err(EmptySafe),
+        [] => Err(EntryNotFound(entry.to_string())),
+        [record] => Ok(record),
+        _ => Err(AmbiguousEntry(entry.to_string())),
+    }
+}
+
+fn value(record: &DbRecord, field: Field) -> Result<&str, TuiPwSafeErrors> {
+    field
+        .value(record)
+        .filter(|v| !v.is_empty())
+        .ok_or_else(|| FieldNotSet(field.name().to_string()))
+}
+
+/// Expiry date, state and path of every expiring password, an error if any has expired
+fn expiring(model: &SafeModel, days: u64) -> Result<(), TuiPwSafeErrors> {
+    let now = now();
+    let records = model.expiring(days);
+    for (record, expiry) in &records {
+        let state = match expiry.checked_sub(now) {
+            Some(left) if left > 0 => format!("in {} days", left.div_ceil(86_400)),
+            _ => "expired".to_string(),
+        };
+        println!(
+            "{}  {:12}  {}/{}",
+            format_time(*expiry),
+            state,
+            record.group().unwrap_or_default(),
+            record.title().unwrap_or_default()
+        );
+    }
+    match records.iter().filter(|(_, expiry)| *expiry <= now).count() {
+        0 => Ok(()),
+        expired => Err(Expired(expired)),
+    }
+}
+
+/// Print a password, the entropy goes to stderr so that only the password is captured
+fn generate(password: String, entropy: f64) -> Result<(), TuiPwSafeErrors> {
+    println!("{}", password);
+    eprintln!("About {:.0} bits of entropy", entropy);
+    Ok(())
+}
+
+fn copy(value: &str, timeout: Duration) -> Result<(), TuiPwSafeErrors> {
+    let mut clipboard = Clipboard::new().map_err(|_| ClipboardUnavailable)?;
+    clipboard
+        .set_text(value)
+        .map_err(|_| ClipboardUnavailable)?;
+    if timeout.is_zero() {
+        eprintln!("Copied to the clipboard");
+    } else {
+        eprintln!(
+            "Copied to the clipboard, it is cleared in {} seconds",
+            timeout.as_secs()
+        );
+    }
+    let until = Some(timeout)
+        .filter(|t| !t.is_zero())
+        .and_then(|t| Instant::now().checked_add(t));
+    // X11 and Wayland serve the clipboard from this process, so it waits until
+    // the timeout or until another application takes the clipboard over
+    if until.is_none() && !cfg!(target_os = "linux") {
+        return Ok(());
+    }
+    while clipboard.get_text().is_ok_and(|text| text == value) {
+        match until {
+            Some(until) if Instant::now() >= until => {
+                let _ = clipboard.clear();
+                break;
+            }
+            _ => thread::sleep(Duration::from_millis(250)),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,728 @@
+//! Command line parsing, without a command the safe is opened in the terminal ui
+use crate::config::{timeout, Config, MAX_TIMEOUT_SECONDS};
+use crate::model::diceware::{Capitalise, Diceware};
+use crate::model::policy::{
+    PasswordPolicy, MAKE_PRONOUNCEABLE, USE_DIGITS, USE_EASY_VISION, USE_HEX_DIGITS, USE_LOWERCASE,
+    USE_SYMBOLS, USE_UPPERCASE,
+};
+use crate::model::record::DbRecord;
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub mod commands;
+
+pub const USAGE: &str = "\
+Usage: tui-pwsafe [COMMAND] <SAFE> [ENTRY] [OPTIONS]
+
+Commands:
+  tui <SAFE>           open the safe in the terminal ui (default)
+  init <SAFE>          create a new, empty safe and open it
+  list <SAFE>          print all groups and their entries
+  show <SAFE> <ENTRY>  print the fields of an entry
+  get <SAFE> <ENTRY>   print a single field of an entry
+  copy <SAFE> <ENTRY>  copy a single field of an entry to the clipboard until it is cleared
+  generate [SAFE]      print a new random password or passphrase, SAFE is only read for --policy
+  expiring <SAFE>      print the entries with expired or expiring passwords, the earliest first
+
+ENTRY is <group>/<title>, or only <title> if no other entry has the same title;
+entries without a group are /<title>.
+Settings and key bindings are read from $XDG_CONFIG_HOME/tui-pwsafe/config.toml.
+
+Options:
+  -f, --field <FIELD>         password, username, url or notes [default: password]
+      --format <FORMAT>       output of list: text, json or tsv [default: text]
+      --show-secrets          print passwords in list and show instead of masking them
+      --reveal-seconds <N>    hide a revealed password again after N seconds [default: 10]
+      --copy-only             never show passwords in the terminal ui, only copy them
+      --clipboard-seconds <N> clear the clipboard N seconds after copying, 0 never [default: 30]
+      --lock-minutes <N>      lock the terminal ui after N idle minutes, 0 never [default: 5]
+      --passphrase-fd <FD>    read the passphrase from a file descriptor instead of the terminal
+      --passphrase-env <VAR>  read the passphrase from an environment variable
+      --length <N>            length of a generated password [default: 12]
+      --classes <LIST>        characters of a generated password, a comma separated list of
+                              lower, upper, digits and symbols, each used at least once or
+                              N times as digits:N [default: lower,upper,digits,symbols]
+      --symbols <CHARS>       symbols to choose from instead of the default set
+      --easy-vision           leave out characters that look alike, such as l, 1, O and 0
+      --pronounceable         generate a password that is easier to pronounce
+      --hex                   generate hexadecimal digits only
+      --policy <NAME>         generate by the password policy the safe keeps under NAME
+      --words <N>             generate a passphrase of N random words [default: 6]
+      --separator <TEXT>      put between the words of a passphrase [default: -]
+      --capitalise <WORDS>    capitalise none, the first, all or random words [default: none]
+      --digits <N>            add N random digits to the words of a passphrase [default: 0]
+      --wordlist <FILE>       words of passphrases, one per line, dice numbers are skipped
+                              [default: the bundled list of 1296 words]
+      --days <N>              expiring lists passwords expiring within N days [default: 30]
+  -h, --help                  print this help
+  -V, --version               print the version
+
+Exit status:
+  0 success, 1 error, 2 usage or config file error, 3 entry or field not found,
+  4 wrong passphrase, 5 more than one entry matches, 6 safe missing, invalid or already existing,
+  7 a password has expired,
+  128+N terminal ui ended by signal N
+";
+
+const COMMANDS: [&str; 8] = [
+    "tui", "init", "list", "show", "get", "copy", "generate", "expiring",
+];
+
+const CLASSES: [(&str, u16); 4] = [
+    ("lower", USE_LOWERCASE),
+    ("upper", USE_UPPERCASE),
+    ("digits", USE_DIGITS),
+    ("symbols", USE_SYMBOLS),
+];
+
+/// Single entry field that can be printed or copied
+#[derive(Debug, Clone, Copy)]
+pub enum Field {
+    Password,
+    Username,
+    Url,
+    Notes,
+}
+
+/// Where the commands without terminal ui take the passphrase from
+#[derive(Debug)]
+pub enum Passphrase {
+    Prompt,
+    Fd(i32),
+    Env(String),
+}
+
+/// Output of the list command
+#[derive(Debug, Clone, Copy)]
+pub enum Format {
+    Text,
+    Json,
+    Tsv,
+}
+
+#[derive(Debug)]
+pub enum Command {
+    Tui,
+    Init,
+    // the flag prints passwords
+    List(Format, bool),
+    Show(String, bool),
+    Get(String, Field),
+    Copy(String, Field),
+    Generate(PasswordPolicy),
+    // a policy kept in the safe
+    GeneratePolicy(String),
+    GeneratePassphrase(Diceware),
+    // days ahead
+    Expiring(u64),
+}
+
+#[derive(Debug)]
+pub struct Cli {
+    pub safe: String,
+    pub command: Command,
+    pub passphrase: Passphrase,
+    pub config: Config,
+}
+
+#[derive(Debug)]
+pub enum Invocation {
+    // boxed, the settings make it much larger than the other cases
+    Run(Box<Cli>),
+    Help,
+    Version,
+}
+
+/// Invalid command line, reported together with a hint to `--help`
+#[derive(Debug)]
+pub struct UsageError(String);
+
+impl Display for UsageError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Field {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "password" => Some(Field::Password),
+            "username" => Some(Field::Username),
+            "url" => Some(Field::Url),
+            "notes" => Some(Field::Notes),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Field::Password => "password",
+            Field::Username => "username",
+            Field::Url => "url",
+            Field::Notes => "notes",
+        }
+    }
+
+    pub fn value<'a>(&self, record: &'a DbRecord) -> Option<&'a str> {
+        match self {
+            Field::Password => record.password(),
+            Field::Username => record.username(),
+            Field::Url => record.url(),
+            Field::Notes => record.notes(),
+        }
+    }
+}
+
+impl Format {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "text" => Some(Format::Text),
+            "json" => Some(Format::Json),
+            "tsv" => Some(Format::Tsv),
+            _ => None,
+        }
+    }
+}
+
+/// Parse the arguments without the program name, options override the given config
+pub fn parse(
+    args: impl IntoIterator<Item = String>,
+    mut config: Config,
+) -> Result<Invocation, UsageError> {
+    let mut args = args.into_iter();
+    let mut positional = Vec::new();
+    let mut field = None;
+    let mut format = None;
+    let mut show_secrets = false;
+    let mut passphrase = Passphrase::Prompt;
+    // options of the terminal ui, remembered to report them for other commands
+    let mut ui_option = None;
+    let mut clipboard_option = false;
+    // options of generate, remembered like those of the terminal ui
+    let mut policy = PasswordPolicy::default();
+    let mut policy_option = None;
+    let mut policy_name = None;
+    let mut diceware = Diceware::default();
+    let mut diceware_option = None;
+    let mut wordlist_option = false;
+    let mut days = None;
+    while let Some(arg) = args.next() {
+        // long options take their value as --name=value or as the next argument
+        let (name, inline) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => {
+                (name.to_string(), Some(value.to_string()))
+            }
+            _ => (arg.clone(), None),
+        };
+        let value = || {
+            inline
+                .or_else(|| args.next())
+                .ok_or_else(|| UsageError(format!("{} needs a value", name)))
+        };
+        match name.as_str() {
+            "-h" | "--help" => return Ok(Invocation::Help),
+            "-V" | "--version" => return Ok(Invocation::Version),
+            "-f" | "--field" => {
+                let value = value()?;
+                field = Some(Field::parse(&value).ok_or_else(|| {
+                    UsageError(format!(
+                        "unknown field '{}', expected password, username, url or notes",
+                        value
+                    ))
+                })?);
+            }
+            "--format" => {
+                let value = value()?;
+                format = Some(Format::parse(&value).ok_or_else(|| {
+                    UsageError(format!(
+                        "unknown format '{}', expected text, json or tsv",
+                        value
+                    ))
+                })?);
+            }
+            "--show-secrets" => show_secrets = true,
+            "--reveal-seconds" => {
+                config.reveal_timeout = seconds(&value()?)?;
+                ui_option = Some(name.clone());
+            }
+            "--clipboard-seconds" => {
+                config.clipboard_timeout = seconds(&value()?)?;
+                clipboard_option = true;
+            }
+            "--copy-only" => {
+                config.copy_only = true;
+                ui_option = Some(name.clone());
+            }
+            "--lock-minutes" => {
+                let value = value()?;
+                config.lock_timeout = value
+                    .parse::<u64>()
+                    .ok()
+                    .and_then(|minutes| minutes.checked_mul(60))
+                    .and_then(timeout)
+                    .ok_or_else(|| {
+                        UsageError(format!(
+                            "invalid number of minutes '{}', at most {}",
+                            value,
+                            MAX_TIMEOUT_SECONDS / 60
+                        ))
+                    })?;
+                ui_option = Some(name.clone());
+            }
+            "--passphrase-fd" => {
+                let value = value()?;
+                passphrase = Passphrase::Fd(
+                    value
+                        .parse()
+                        .map_err(|_| UsageError(format!("invalid file descriptor '{}'", value)))?,
+                );
+            }
+            "--passphrase-env" => passphrase = Passphrase::Env(value()?),
+            "--length" => {
+                let value = value()?;
+                policy.length = value
+                    .parse()
+                    .map_err(|_| UsageError(format!("invalid length '{}'", value)))?;
+                policy_option = Some(name.clone());
+            }
+            "--classes" => {
+                parse_classes(&value()?, &mut policy)?;
+                policy_option = Some(name.clone());
+            }
+            "--symbols" => {
+                policy.symbols = Some(value()?).filter(|s| !s.is_empty());
+                policy_option = Some(name.clone());
+            }
+            "--easy-vision" | "--pronounceable" | "--hex" => {
+                policy.flags |= match name.as_str() {
+                    "--easy-vision" => USE_EASY_VISION,
+                    "--pronounceable" => MAKE_PRONOUNCEABLE,
+                    _ => USE_HEX_DIGITS,
+                };
+                policy_option = Some(name.clone());
+            }
+            "--policy" => policy_name = Some(value()?),
+            "--words" | "--digits" => {
+                let value = value()?;
+                let count = value
+                    .parse()
+                    .map_err(|_| UsageError(format!("invalid number '{}'", value)))?;
+                match name.as_str() {
+                    "--words" => diceware.words = count,
+                    _ => diceware.digits = count,
+                }
+                diceware_option = Some(name.clone());
+            }
+            "--separator" => {
+                diceware.separator = value()?;
+                diceware_option = Some(name.clone());
+            }
+            "--capitalise" => {
+                let value = value()?;
+                diceware.capitalise = Capitalise::parse(&value).ok_or_else(|| {
+                    UsageError(format!(
+                        "unknown way to capitalise '{}', expected none, first, all or random",
+                        value
+                    ))
+                })?;
+                diceware_option = Some(name.clone());
+            }
+            "--wordlist" => {
+                config.wordlist = Some(PathBuf::from(value()?));
+                wordlist_option = true;
+            }
+            "--days" => {
+                let value = value()?;
+                days = Some(
+                    value
+                        .parse()
+                        .map_err(|_| UsageError(format!("invalid number of days '{}'", value)))?,
+                );
+            }
+            "--" => positional.extend(args.by_ref()),
+            _ if name.starts_with('-') && name.len() > 1 => {
+                return Err(UsageError(format!("unknown option '{}'", name)))
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    let (command, rest) = match positional.split_first() {
+        Some((command, rest)) if COMMANDS.contains(&command.as_str()) => (command.as_str(), rest),
+        _ => ("tui", positional.as_slice()),
+    };
+    let takes_entry = matches!(command, "show" | "get" | "copy");
+    // generate needs no safe unless it takes the policy from it
+    let takes_safe = command != "generate" || policy_name.is_some();
+    let safe = match rest.first() {
+        Some(safe) if takes_safe => safe.clone(),
+        None if command == "generate" && takes_safe => {
+            return Err(UsageError("--policy needs the safe file".to_string()))
+        }
+        None if takes_safe => return Err(UsageError("missing the safe file".to_string())),
+        _ => String::new(),
+    };
+    let entry = rest.get(1).cloned();
+    let arguments = match (takes_entry, takes_safe) {
+        (true, _) => 2,
+        (false, true) => 1,
+        (false, false) => 0,
+    };
+    if let Some(extra) = rest.get(arguments) {
+        return Err(UsageError(format!("unexpected argument '{}'", extra)));
+    }
+    if field.is_some() && !matches!(command, "get" | "copy") {
+        return Err(UsageError(format!("--field is not used by {}", command)));
+    }
+    if format.is_some() && command != "list" {
+        return Err(UsageError(format!("--format is not used by {}", command)));
+    }
+    if show_secrets && !matches!(command, "list" | "show") {
+        return Err(UsageError(format!(
+            "--show-secrets is not used by {}",
+            command
+        )));
+    }
+    if !matches!(passphrase, Passphrase::Prompt) && matches!(command, "tui" | "init") {
+        return Err(UsageError(format!(
+            "{} asks for the passphrase itself",
+            command
+        )));
+    }
+    if let Some(option) = ui_option.filter(|_| !matches!(command, "tui" | "init")) {
+        return Err(UsageError(format!("{} is not used by {}", option, command)));
+    }
+    if clipboard_option && !matches!(command, "tui" | "init" | "copy") {
+        return Err(UsageError(format!(
+            "--clipboard-seconds is not used by {}",
+            command
+        )));
+    }
+    if let Some(option) = policy_option.as_ref().filter(|_| command != "generate") {
+        return Err(UsageError(format!("{} is not used by {}", option, command)));
+    }
+    if policy_name.is_some() && command != "generate" {
+        return Err(UsageError(format!("--policy is not used by {}", command)));
+    }
+    if let (Some(option), Some(_)) = (&policy_option, &policy_name) {
+        return Err(UsageError(format!(
+            "{} can't be combined with --policy",
+            option
+        )));
+    }
+    if let Some(option) = diceware_option.as_ref().filter(|_| command != "generate") {
+        return Err(UsageError(format!("{} is not used by {}", option, command)));
+    }
+    if wordlist_option && !matches!(command, "tui" | "init" | "generate") {
+        return Err(UsageError(format!("--wordlist is not used by {}", command)));
+    }
+    // the wordlist alone asks for a passphrase too
+    let passphrase_option = diceware_option.or_else(|| {
+        wordlist_option
+            .then(|| "--wordlist".to_string())
+            .filter(|_| command == "generate")
+    });
+    if let Some(option) = &passphrase_option {
+        let other = policy_option
+            .clone()
+            .or_else(|| policy_name.as_ref().map(|_| "--policy".to_string()));
+        if let Some(other) = other {
+            return Err(UsageError(format!(
+                "{} can't be combined with {}",
+                option, other
+            )));
+        }
+        diceware.check().map_err(|e| UsageError(e.to_string()))?;
+    } else if command == "generate" && policy_name.is_none() {
+        policy.check().map_err(|e| UsageError(e.to_string()))?;
+    }
+    if days.is_some() && command != "expiring" {
+        return Err(UsageError(format!("--days is not used by {}", command)));
+    }
+    let entry = || entry.ok_or_else(|| UsageError(format!("{} needs an entry", command)));
+    let field = field.unwrap_or(Field::Password);
+    let command = match command {
+        "init" => Command::Init,
+        "list" => Command::List(format.unwrap_or(Format::Text), show_secrets),
+        "show" => Command::Show(entry()?, show_secrets),
+        "get" => Command::Get(entry()?, field),
+        "copy" => Command::Copy(entry()?, field),
+        "generate" => match (policy_name, passphrase_option) {
+            (Some(name), _) => Command::GeneratePolicy(name),
+            (None, Some(_)) => Command::GeneratePassphrase(diceware),
+            (None, None) => Command::Generate(policy),
+        },
+        "expiring" => Command::Expiring(days.unwrap_or(config.expiring_days)),
+        _ => Command::Tui,
+    };
+    Ok(Invocation::Run(Box::new(Cli {
+        safe,
+        command,
+        passphrase,
+        config,
+    })))
+}
+
+/// Character classes of `--classes`, e.g. `lower,upper,digits:2`
+fn parse_classes(list: &str, policy: &mut PasswordPolicy) -> Result<(), UsageError> {
+    policy.flags &= !CLASSES.iter().fold(0, |flags, (_, flag)| flags | flag);
+    for class in list.split(',').map(str::trim) {
+        let (name, min) = match class.split_once(':') {
+            Some((name, min)) => (
+                name,
+                min.parse()
+                    .map_err(|_| UsageError(format!("invalid count in '{}'", class)))?,
+            ),
+            None => (class, 1),
+        };
+        let (_, flag) = CLASSES.iter().find(|(n, _)| *n == name).ok_or_else(|| {
+            UsageError(format!(
+                "unknown class '{}', expected lower, upper, digits or symbols",
+                name
+            ))
+        })?;
+        policy.flags |= flag;
+        policy.set_min_count(*flag, min);
+    }
+    Ok(())
+}
+
+/// Timeout of an option in seconds
+fn seconds(value: &str) -> Result<Duration, UsageError> {
+    value.parse().ok().and_then(timeout).ok_or_else(|| {
+        UsageError(format!(
+            "invalid number of seconds '{}', at most {}",
+            value, MAX_TIMEOUT_SECONDS
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(args: &[&str]) -> Result<Cli, String> {
+        match parse(args.iter().map(|a| a.to_string()), Config::default()) {
+            Ok(Invocation::Run(cli)) => Ok(*cli),
+            Ok(other) => Err(format!("not a run: {:?}", other)),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    fn usage_error(args: &[&str]) -> String {
+        match run(args) {
+            Ok(cli) => panic!("{:?} parsed as {:?}", args, cli.command),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn without_command_the_terminal_ui_opens() {
+        let cli = run(&["a.psafe3"]).unwrap();
+        assert!(matches!(cli.command, Command::Tui));
+        assert_eq!(cli.safe, "a.psafe3");
+        assert!(matches!(cli.passphrase, Passphrase::Prompt));
+        assert!(matches!(
+            run(&["tui", "a.psafe3"]).unwrap().command,
+            Command::Tui
+        ));
+    }
+
+    #[test]
+    fn commands() {
+        assert!(matches!(
+            run(&["init", "a"]).unwrap().command,
+            Command::Init
+        ));
+        assert!(matches!(
+            run(&["list", "a"]).unwrap().command,
+            Command::List(Format::Text, false)
+        ));
+        assert!(matches!(
+            run(&["list", "a", "--format=tsv", "--show-secrets"])
+                .unwrap()
+                .command,
+            Command::List(Format::Tsv, true)
+        ));
+        assert!(matches!(
+            run(&["show", "a", "Work/mail"]).unwrap().command,
+            Command::Show(entry, false) if entry == "Work/mail"
+        ));
+        assert!(matches!(
+            run(&["get", "a", "mail"]).unwrap().command,
+            Command::Get(entry, Field::Password) if entry == "mail"
+        ));
+        assert!(matches!(
+            run(&["copy", "a", "mail", "-f", "url"]).unwrap().command,
+            Command::Copy(entry, Field::Url) if entry == "mail"
+        ));
+        assert!(matches!(
+            run(&["expiring", "a"]).unwrap().command,
+            Command::Expiring(30)
+        ));
+        assert!(matches!(
+            run(&["expiring", "a", "--days", "7"]).unwrap().command,
+            Command::Expiring(7)
+        ));
+        let cli = run(&["get", "a", "mail", "--passphrase-env", "PW"]).unwrap();
+        assert!(matches!(cli.passphrase, Passphrase::Env(name) if name == "PW"));
+        let cli = run(&["get", "a", "mail", "--passphrase-fd=3"]).unwrap();
+        assert!(matches!(cli.passphrase, Passphrase::Fd(3)));
+    }
+
+    #[test]
+    fn generate() {
+        let cli = run(&["generate", "--length", "20", "--classes", "lower,digits:3"]).unwrap();
+        assert_eq!(cli.safe, "");
+        match cli.command {
+            Command::Generate(policy) => {
+                assert_eq!(policy.length, 20);
+                assert_eq!(policy.flags, USE_LOWERCASE | USE_DIGITS);
+                assert_eq!(policy.min_digits, 3);
+            }
+            other => panic!("{:?}", other),
+        }
+        assert!(matches!(
+            run(&["generate", "a", "--policy", "web"]).unwrap().command,
+            Command::GeneratePolicy(name) if name == "web"
+        ));
+        match run(&["generate", "--words", "4", "--separator", " "])
+            .unwrap()
+            .command
+        {
+            Command::GeneratePassphrase(diceware) => {
+                assert_eq!(diceware.words, 4);
+                assert_eq!(diceware.separator, " ");
+            }
+            other => panic!("{:?}", other),
+        }
+        assert!(matches!(
+            run(&["generate", "--wordlist", "words.txt"])
+                .unwrap()
+                .command,
+            Command::GeneratePassphrase(_)
+        ));
+    }
+
+    #[test]
+    fn help_and_version() {
+        for (args, help) in [
+            (&["--help"][..], true),
+            (&["list", "a", "-h"], true),
+            (&["-V"], false),
+            (&["--version", "--unknown"], false),
+        ] {
+            match parse(args.iter().map(|a| a.to_string()), Config::default()) {
+                Ok(Invocation::Help) => assert!(help, "{:?}", args),
+                Ok(Invocation::Version) => assert!(!help, "{:?}", args),
+                _ => panic!("{:?}", args),
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_options_and_values() {
+        assert_eq!(
+            usage_error(&["a", "--unknown"]),
+            "unknown option '--unknown'"
+        );
+        assert_eq!(usage_error(&["a", "-x"]), "unknown option '-x'");
+        assert_eq!(
+            usage_error(&["get", "a", "mail", "--field", "pin"]),
+            "unknown field 'pin', expected password, username, url or notes"
+        );
+        assert_eq!(
+            usage_error(&["list", "a", "--format"]),
+            "--format needs a value"
+        );
+        assert_eq!(
+            usage_error(&["a", "--lock-minutes", "-1"]),
+            format!(
+                "invalid number of minutes '-1', at most {}",
+                MAX_TIMEOUT_SECONDS / 60
+            )
+        );
+        assert_eq!(
+            usage_error(&["generate", "--classes", "lower,emoji"]),
+            "unknown class 'emoji', expected lower, upper, digits or symbols"
+        );
+    }
+
+    #[test]
+    fn a_repeated_option_takes_the_last_value() {
+        assert!(matches!(
+            run(&["get", "a", "mail", "-f", "url", "--field=notes"])
+                .unwrap()
+                .command,
+            Command::Get(_, Field::Notes)
+        ));
+        let cli = run(&["a", "--reveal-seconds", "3", "--reveal-seconds", "4"]).unwrap();
+        assert_eq!(cli.config.reveal_timeout, Duration::from_secs(4));
+        // the classes are replaced, not added up
+        match run(&["generate", "--classes", "upper", "--classes", "lower"])
+            .unwrap()
+            .command
+        {
+            Command::Generate(policy) => assert_eq!(policy.flags, USE_LOWERCASE),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn options_of_other_commands() {
+        assert_eq!(
+            usage_error(&["list", "a", "--field", "url"]),
+            "--field is not used by list"
+        );
+        assert_eq!(
+            usage_error(&["show", "a", "mail", "--format", "json"]),
+            "--format is not used by show"
+        );
+        assert_eq!(
+            usage_error(&["get", "a", "mail", "--show-secrets"]),
+            "--show-secrets is not used by get"
+        );
+        assert_eq!(
+            usage_error(&["a", "--passphrase-env", "PW"]),
+            "tui asks for the passphrase itself"
+        );
+        assert_eq!(
+            usage_error(&["list", "a", "--copy-only"]),
+            "--copy-only is not used by list"
+        );
+        assert_eq!(
+            usage_error(&["get", "a", "mail", "--clipboard-seconds", "5"]),
+            "--clipboard-seconds is not used by get"
+        );
+        assert_eq!(
+            usage_error(&["a", "--length", "8"]),
+            "--length is not used by tui"
+        );
+        assert_eq!(
+            usage_error(&["generate", "a", "--policy", "web", "--hex"]),
+            "--hex can't be combined with --policy"
+        );
+        assert_eq!(
+            usage_error(&["generate", "--words", "4", "--length", "8"]),
+            "--words can't be combined with --length"
+        );
+        assert_eq!(
+            usage_error(&["list", "a", "--days", "3"]),
+            "--days is not used by list"
+        );
+    }
+
+    #[test]
+    fn missing_and_extra_arguments() {
+        assert_eq!(usage_error(&[]), "missing the safe file");
+        assert_eq!(usage_error(&["list"]), "missing the safe file");
+        assert_eq!(
+            usage_error(&["generate", "--policy", "web"]),
+            "--policy needs the safe file"
+        );
+        assert_eq!(usage_error(&["show", "a"]), "show needs an entry");
+        assert_eq!(usage_error(&["list", "a", "b"]), "unexpected argument 'b'");
+        assert_eq!(usage_error(&["generate", "a"]), "unexpected argument 'a'");
+    }
+}
@@ -0,0 +1,296 @@
+//! Settings of the terminal ui and the clipboard, read from
+//! `$XDG_CONFIG_HOME/tui-pwsafe/config.toml` and overridden by the command line
+
+use crate::contracts::TuiPwSafeErrors;
+use crate::keymap::{Action, KeyChord, KeyMap};
+use std::path::PathBuf;
+use std::time::Duration;
+use std::{env, fs, io};
+use toml::{Table, Value};
+
+/// How long a revealed password stays visible unless configured otherwise
+pub const DEFAULT_REVEAL_SECONDS: u64 = 10;
+/// How long a copied value stays in the clipboard unless configured otherwise
+pub const DEFAULT_CLIPBOARD_SECONDS: u64 = 30;
+/// Idle time after which the safe is locked unless configured otherwise
+pub const DEFAULT_LOCK_MINUTES: u64 = 5;
+/// Days ahead in which expiring passwords are listed unless configured otherwise
+pub const DEFAULT_EXPIRING_DAYS: u64 = 30;
+/// Longest timeout in seconds, the instant it ends at has to fit on every platform
+pub const MAX_TIMEOUT_SECONDS: u64 = u32::MAX as u64;
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub reveal_timeout: Duration,
+    // passwords are never shown, they can only be copied
+    pub copy_only: bool,
+    // zero keeps copied values in the clipboard
+    pub clipboard_timeout: Duration,
+    // zero never locks the safe
+    pub lock_timeout: Duration,
+    // passwords expiring within these days are listed with the expired ones
+    pub expiring_days: u64,
+    pub keys: KeyMap,
+    // words of generated passphrases, the bundled list if not set
+    pub wordlist: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            reveal_timeout: Duration::from_secs(DEFAULT_REVEAL_SECONDS),
+            copy_only: false,
+            clipboard_timeout: Duration::from_secs(DEFAULT_CLIPBOARD_SECONDS),
+            lock_timeout: Duration::from_secs(DEFAULT_LOCK_MINUTES * 60),
+            expiring_days: DEFAULT_EXPIRING_DAYS,
+            keys: KeyMap::default(),
+            wordlist: None,
+        }
+    }
+}
+
+impl Config {
+    /// Settings of the config file, the defaults when there is none
+    pub fn load() -> Result<Self, TuiPwSafeErrors> {
+        let Some(path) = path() else {
+            return Ok(Config::default());
+        };
+        let invalid =
+            |e: String| TuiPwSafeErrors::InvalidConfig(format!("{}: {}", path.display(), e));
+        match fs::read_to_string(&path) {
+            Ok(text) => Self::parse(&text).map_err(invalid),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(invalid(e.to_string())),
+        }
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        let table: Table = text
+            .parse()
+            .map_err(|e: toml::de::Error| e.message().to_string())?;
+        let mut config = Config::default();
+        // the preset is the base of the single key bindings
+        if let Some(preset) = table.get("preset") {
+            let name = preset.as_str().unwrap_or_default();
+            config.keys = KeyMap::preset(name).ok_or_else(|| {
+                format!("unknown preset {}, expected default, vim or emacs", preset)
+            })?;
+        }
+        for (name, value) in &table {
+            match name.as_str() {
+                "preset" => {}
+                "reveal-seconds" => config.reveal_timeout = seconds(name, value)?,
+                "clipboard-seconds" => config.clipboard_timeout = seconds(name, value)?,
+                "lock-minutes" => {
+                    config.lock_timeout = number(name, value)?
+                        .checked_mul(60)
+                        .and_then(timeout)
+                        .ok_or_else(|| {
+                            format!(
+                                "{} must be a number of minutes up to {}",
+                                name,
+                                MAX_TIMEOUT_SECONDS / 60
+                            )
+                        })?
+                }
+                "expiring-days" => config.expiring_days = number(name, value)?,
+                "copy-only" => {
+                    config.copy_only = value
+                        .as_bool()
+                        .ok_or_else(|| format!("{} must be true or false", name))?
+                }
+                "wordlist" => {
+                    let path = value
+                        .as_str()
+                        .ok_or_else(|| format!("{} must be the path of a file", name))?;
+                    config.wordlist = Some(PathBuf::from(path))
+                }
+                "keys" => {
+                    let keys = value
+                        .as_table()
+                        .ok_or_else(|| "keys must be a table of actions".to_string())?;
+                    for (action, chords) in keys {
+                        let chords = key_chords(action, chords)?;
+                        let action = Action::parse(action)
+                            .ok_or_else(|| format!("unknown action '{}'", action))?;
+                        config.keys.bind(action, &chords);
+                    }
+                }
+                _ => return Err(format!("unknown setting '{}'", name)),
+            }
+        }
+        Ok(config)
+    }
+}
+
+fn path() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("tui-pwsafe").join("config.toml"))
+}
+
+fn number(name: &str, value: &Value) -> Result<u64, String> {
+    value
+        .as_integer()
+        .and_then(|n| u64::try_from(n).ok())
+        .ok_or_else(|| format!("{} must be a positive number", name))
+}
+
+fn seconds(name: &str, value: &Value) -> Result<Duration, String> {
+    timeout(number(name, value)?).ok_or_else(|| {
+        format!(
+            "{} must be a number of seconds up to {}",
+            name, MAX_TIMEOUT_SECONDS
+        )
+    })
+}
+
+/// Timeout of the seconds, none if they are more than [MAX_TIMEOUT_SECONDS]
+pub fn timeout(seconds: u64) -> Option<Duration> {
+    (seconds <= MAX_TIMEOUT_SECONDS).then(|| Duration::from_secs(seconds))
+}
+
+/// A single chord or a list of them, an empty list unbinds the action
+fn key_chords(action: &str, value: &Value) -> Result<Vec<KeyChord>, String> {
+    let values = match value {
+        Value::Array(values) => values.iter().collect(),
+        value => vec![value],
+    };
+    values
+        .into_iter()
+        .map(|v| {
+            v.as_str()
+                .and_then(KeyChord::parse)
+                .ok_or_else(|| format!("invalid key {} for {}", v, action))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    fn parse(text: &str) -> Config {
+        Config::parse(text).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn error(text: &str) -> String {
+        match Config::parse(text) {
+            Ok(_) => panic!("{} was accepted", text),
+            Err(e) => e,
+        }
+    }
+
+    fn action(config: &Config, code: KeyCode, modifiers: KeyModifiers) -> Option<Action> {
+        config.keys.action(&KeyEvent::new(code, modifiers))
+    }
+
+    #[test]
+    fn defaults() {
+        let config = parse("");
+        assert_eq!(config.reveal_timeout, Duration::from_secs(10));
+        assert_eq!(config.clipboard_timeout, Duration::from_secs(30));
+        assert_eq!(config.lock_timeout, Duration::from_secs(300));
+        assert_eq!(config.expiring_days, 30);
+        assert!(!config.copy_only);
+        assert_eq!(config.wordlist, None);
+        assert_eq!(config.keys.keys(Action::Quit), "<Esc> <Ctrl-c>");
+    }
+
+    #[test]
+    fn settings() {
+        let config = parse(
+            "reveal-seconds = 3\nclipboard-seconds = 0\nlock-minutes = 2\n\
+             expiring-days = 7\ncopy-only = true\nwordlist = \"words.txt\"\n",
+        );
+        assert_eq!(config.reveal_timeout, Duration::from_secs(3));
+        assert_eq!(config.clipboard_timeout, Duration::ZERO);
+        assert_eq!(config.lock_timeout, Duration::from_secs(120));
+        assert_eq!(config.expiring_days, 7);
+        assert!(config.copy_only);
+        assert_eq!(config.wordlist, Some(PathBuf::from("words.txt")));
+    }
+
+    #[test]
+    fn invalid_settings() {
+        assert_eq!(error("colour = 1"), "unknown setting 'colour'");
+        assert_eq!(error("copy-only = 1"), "copy-only must be true or false");
+        assert_eq!(
+            error("reveal-seconds = -1"),
+            "reveal-seconds must be a positive number"
+        );
+        assert_eq!(
+            error(&format!("clipboard-seconds = {}", MAX_TIMEOUT_SECONDS + 1)),
+            format!(
+                "clipboard-seconds must be a number of seconds up to {}",
+                MAX_TIMEOUT_SECONDS
+            )
+        );
+        assert_eq!(
+            error(&format!("lock-minutes = {}", i64::MAX)),
+            format!(
+                "lock-minutes must be a number of minutes up to {}",
+                MAX_TIMEOUT_SECONDS / 60
+            )
+        );
+        assert_eq!(error("wordlist = 1"), "wordlist must be the path of a file");
+        assert!(!error("reveal-seconds = ").is_empty());
+    }
+
+    #[test]
+    fn key_bindings() {
+        let config = parse("[keys]\nquit = \"ctrl-q\"\nsave = [\"ctrl-s\", \"f2\"]\nlock = []\n");
+        assert_eq!(
+            action(&config, KeyCode::Char('q'), KeyModifiers::CONTROL),
+            Some(Action::Quit)
+        );
+        assert_eq!(action(&config, KeyCode::Esc, KeyModifiers::NONE), None);
+        assert_eq!(
+            action(&config, KeyCode::F(2), KeyModifiers::NONE),
+            Some(Action::Save)
+        );
+        assert_eq!(config.keys.key(Action::Lock), None);
+    }
+
+    #[test]
+    fn bad_bindings() {
+        assert_eq!(
+            error("[keys]\nquit = \"hyper-q\""),
+            "invalid key \"hyper-q\" for quit"
+        );
+        assert_eq!(error("[keys]\nquit = 5"), "invalid key 5 for quit");
+        assert_eq!(
+            error("[keys]\nquit = [\"q\", true]"),
+            "invalid key true for quit"
+        );
+        assert_eq!(error("[keys]\nfly = \"f\""), "unknown action 'fly'");
+        assert_eq!(error("keys = \"vim\""), "keys must be a table of actions");
+    }
+
+    #[test]
+    fn bindings_on_top_of_a_preset() {
+        let config = parse("preset = \"vim\"\n[keys]\nedit = \"y\"\n");
+        // y moves from copying the password to editing
+        assert_eq!(
+            action(&config, KeyCode::Char('y'), KeyModifiers::NONE),
+            Some(Action::Edit)
+        );
+        assert_eq!(config.keys.key(Action::CopyPassword), None);
+        // the other keys of the preset stay
+        assert_eq!(
+            action(&config, KeyCode::Char('q'), KeyModifiers::NONE),
+            Some(Action::Quit)
+        );
+        assert_eq!(
+            error("preset = \"nano\""),
+            "unknown preset \"nano\", expected default, vim or emacs"
+        );
+        assert_eq!(
+            error("preset = 1"),
+            "unknown preset 1, expected default, vim or emacs"
+        );
+    }
+}
@@ -1,45 +1,110 @@
-use crate::contracts::TuiPwSafeErrors::{StoreFileNotFound, StoreFileNotRead, UnknownError};
+use crate::contracts::TuiPwSafeErrors::{
+    AmbiguousEntry, ClipboardUnavailable, CorruptFile, EmptySafe, EntryNotFound, Expired,
+    FieldNotSet, InvalidConfig, InvalidOtpAuth, InvalidPolicy, InvalidWordlist, IoError,
+    PassphraseUnavailable, PolicyNotFound, StoreFileExists, StoreFileNotFound, StoreFileNotRead,
+    Terminated, UntitledRecord, WrongPassphrase,
+};
+use crate::keymap::Action;
 use crate::SafeModel;
 use crossterm::event::KeyEvent;
-use rs_pwsafe::pwserrors::PwSafeError;
 use std::fmt::{Display, Formatter};
+use std::io;
 use tui::backend::Backend;
 use tui::layout::Rect;
 use tui::Frame;
+use uuid::Uuid;
 
 // TuiPwSafeError
 pub enum TuiPwSafeErrors {
     StoreFileNotFound, //:&str = "PwSafe Store file not specified",
     StoreFileNotRead,
-    UnknownError(PwSafeError),
+    StoreFileExists,
+    WrongPassphrase,
+    CorruptFile,
+    EntryNotFound(String),
+    AmbiguousEntry(String),
+    FieldNotSet(String),
+    ClipboardUnavailable,
+    PassphraseUnavailable(String),
+    EmptySafe,
+    UntitledRecord,
+    Terminated(i32),
+    InvalidConfig(String),
+    InvalidPolicy(String),
+    InvalidOtpAuth(String),
+    InvalidWordlist(String),
+    PolicyNotFound(String),
+    // number of entries with an expired password
+    Expired(usize),
+    IoError(io::Error),
 }
 
 impl Display for TuiPwSafeErrors {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            StoreFileNotFound => write!(f, "PwSafe Store file not specified"),
+            StoreFileNotFound => write!(f, "PwSafe Store file not found"),
             StoreFileNotRead => write!(f, "Store file is not read"),
-            UnknownError(e) => write!(f, "Unexpected Error {:?}", e),
+            StoreFileExists => write!(f, "Store file already exists"),
+            WrongPassphrase => write!(f, "Wrong passphrase"),
+            CorruptFile => write!(f, "Store file is not a valid pwsafe v3 file"),
+            EntryNotFound(name) => write!(f, "No entry matches {}", name),
+            AmbiguousEntry(name) => write!(f, "More than one entry matches {}", name),
+            FieldNotSet(name) => write!(f, "Field {} is not set", name),
+            ClipboardUnavailable => write!(f, "Clipboard is not available"),
+            PassphraseUnavailable(source) => write!(f, "Can't read the passphrase from {}", source),
+            EmptySafe => write!(f, "The safe has no entries"),
+            UntitledRecord => write!(f, "An entry needs a title"),
+            Terminated(signal) => write!(f, "Terminated by signal {}", signal),
+            InvalidConfig(problem) => write!(f, "Invalid config file {}", problem),
+            InvalidPolicy(problem) => write!(f, "Invalid password policy: {}", problem),
+            InvalidOtpAuth(problem) => write!(f, "Invalid otpauth URI: {}", problem),
+            InvalidWordlist(problem) => write!(f, "Invalid wordlist {}", problem),
+            PolicyNotFound(name) => write!(f, "No password policy named {}", name),
+            Expired(1) => write!(f, "1 password has expired"),
+            Expired(count) => write!(f, "{} passwords have expired", count),
+            IoError(e) => write!(f, "I/O error: {}", e),
         }
     }
 }
 
-impl From<PwSafeError> for TuiPwSafeErrors {
-    fn from(error: PwSafeError) -> Self {
-        match error {
-            PwSafeError::FileNotFound => StoreFileNotFound,
-            PwSafeError::InvalidKey => StoreFileNotRead,
-            other => UnknownError(other),
+impl TuiPwSafeErrors {
+    /// Exit status of the command line, scripts tell these cases apart
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            EntryNotFound(_) | FieldNotSet(_) | EmptySafe | PolicyNotFound(_) => 3,
+            InvalidConfig(_) | InvalidWordlist(_) => 2,
+            WrongPassphrase => 4,
+            AmbiguousEntry(_) => 5,
+            StoreFileNotFound | StoreFileExists | CorruptFile => 6,
+            Expired(_) => 7,
+            // like a shell reports a process killed by a signal
+            Terminated(signal) => 128 + signal,
+            _ => 1,
         }
     }
 }
 
+impl From<io::Error> for TuiPwSafeErrors {
+    fn from(error: io::Error) -> Self {
+        IoError(error)
+    }
+}
+
 // Traits
 pub trait UiWidgetVm<B: Backend> {
     fn capture_key(&mut self, key: KeyEvent);
     fn draw(&mut self, f: &mut Frame<B>, rec: Rect);
     fn update_model(&mut self, model: &mut SafeModel);
     fn is_done(&self) -> bool;
+    // a popup or a search inside the view consumes <Esc> instead of the app
+    fn has_popup(&self) -> bool;
+    // typed characters go to a text input of the view instead of triggering actions
+    fn takes_text(&self) -> bool;
+    // selected group and entry, restored when the safe is unlocked again
+    fn location(&self) -> Option<(String, Option<Uuid>)>;
+    // actions of the view for the command palette, quit and lock are added by the app
+    fn commands(&self) -> Vec<Action>;
+    fn run_command(&mut self, action: Action);
 }
 
 // Const functions
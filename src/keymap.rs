@@ -0,0 +1,553 @@
+//! Named actions of the terminal ui and the key chords bound to them
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    Lock,
+    CommandPalette,
+    NextPane,
+    Up,
+    Down,
+    Top,
+    Bottom,
+    PageUp,
+    PageDown,
+    Expand,
+    Collapse,
+    Select,
+    ScrollUp,
+    ScrollDown,
+    Search,
+    NextMatch,
+    PreviousMatch,
+    CopyPassword,
+    CopyUsername,
+    CopyTotp,
+    SetTotp,
+    Reveal,
+    Edit,
+    Generate,
+    NewEntry,
+    NewGroup,
+    Delete,
+    Move,
+    Undo,
+    Redo,
+    Save,
+    ChangeMasterPassword,
+}
+
+/// Every action with the name used in the config file and the description of the command palette
+const ACTIONS: [(Action, &str, &str); 33] = [
+    (Action::Quit, "quit", "Quit"),
+    (Action::Lock, "lock", "Lock the safe"),
+    (
+        Action::CommandPalette,
+        "command-palette",
+        "Show all commands",
+    ),
+    (
+        Action::NextPane,
+        "next-pane",
+        "Switch between groups and entries",
+    ),
+    (Action::Up, "up", "Select the previous row"),
+    (Action::Down, "down", "Select the next row"),
+    (Action::Top, "top", "Go to the first row"),
+    (Action::Bottom, "bottom", "Go to the last row"),
+    (Action::PageUp, "page-up", "Move half a page up"),
+    (Action::PageDown, "page-down", "Move half a page down"),
+    (Action::Expand, "expand", "Expand the group"),
+    (Action::Collapse, "collapse", "Collapse the group"),
+    (Action::Select, "select", "Expand or collapse the group"),
+    (Action::ScrollUp, "scroll-up", "Scroll the entry up"),
+    (Action::ScrollDown, "scroll-down", "Scroll the entry down"),
+    (Action::Search, "search", "Search all entries"),
+    (
+        Action::NextMatch,
+        "next-match",
+        "Go to the next search result",
+    ),
+    (
+        Action::PreviousMatch,
+        "previous-match",
+        "Go to the previous search result",
+    ),
+    (Action::CopyPassword, "copy-password", "Copy the password"),
+    (Action::CopyUsername, "copy-username", "Copy the username"),
+    (
+        Action::CopyTotp,
+        "copy-totp",
+        "Copy the one-time code of the two-factor key",
+    ),
+    (
+        Action::SetTotp,
+        "set-totp",
+        "Set up one-time codes from an otpauth URI",
+    ),
+    (Action::Reveal, "reveal", "Show or hide the password"),
+    (Action::Edit, "edit", "Edit the entry"),
+    (
+        Action::Generate,
+        "generate-password",
+        "Generate a password for the entry or the clipboard",
+    ),
+    (Action::NewEntry, "new-entry", "Add an entry to the group"),
+    (Action::NewGroup, "new-group", "Add a group"),
+    (Action::Delete, "delete", "Delete the entry"),
+    (Action::Move, "move", "Move the entry to another group"),
+    (Action::Undo, "undo", "Undo the last change"),
+    (Action::Redo, "redo", "Redo the last undone change"),
+    (Action::Save, "save", "Save the safe"),
+    (
+        Action::ChangeMasterPassword,
+        "change-master-password",
+        "Change the master password",
+    ),
+];
+
+const DEFAULT_KEYS: [(Action, &[&str]); 33] = [
+    (Action::Quit, &["esc", "ctrl-c"]),
+    (Action::Lock, &["ctrl-l"]),
+    (Action::CommandPalette, &[":", "ctrl-k"]),
+    (Action::NextPane, &["tab"]),
+    (Action::Up, &["up", "k"]),
+    (Action::Down, &["down", "j"]),
+    (Action::Top, &["g", "home"]),
+    (Action::Bottom, &["G", "end"]),
+    (Action::PageUp, &["ctrl-u"]),
+    (Action::PageDown, &["ctrl-d"]),
+    (Action::Expand, &["right"]),
+    (Action::Collapse, &["left"]),
+    (Action::Select, &["enter"]),
+    (Action::ScrollUp, &["pgup"]),
+    (Action::ScrollDown, &["pgdn"]),
+    (Action::Search, &["/", "ctrl-f"]),
+    (Action::NextMatch, &["n"]),
+    (Action::PreviousMatch, &["N"]),
+    (Action::CopyPassword, &["ctrl-p"]),
+    (Action::CopyUsername, &["alt-u"]),
+    (Action::CopyTotp, &["ctrl-o"]),
+    (Action::SetTotp, &["ctrl-a"]),
+    (Action::Reveal, &["ctrl-r"]),
+    (Action::Edit, &["ctrl-e"]),
+    (Action::Generate, &["ctrl-t"]),
+    (Action::NewEntry, &["ctrl-n"]),
+    (Action::NewGroup, &["ctrl-g"]),
+    (Action::Delete, &["del"]),
+    (Action::Move, &["ctrl-x"]),
+    (Action::Undo, &["ctrl-z"]),
+    (Action::Redo, &["ctrl-y"]),
+    (Action::Save, &["ctrl-s"]),
+    (Action::ChangeMasterPassword, &["ctrl-w"]),
+];
+
+// presets only list what differs from the default keys
+const VIM_KEYS: [(Action, &[&str]); 18] = [
+    (Action::Quit, &["q", "ctrl-c"]),
+    (Action::Expand, &["l", "right"]),
+    (Action::Collapse, &["h", "left"]),
+    (Action::ScrollUp, &["ctrl-y", "pgup"]),
+    (Action::ScrollDown, &["ctrl-e", "pgdn"]),
+    (Action::Search, &["/"]),
+    (Action::CopyPassword, &["y"]),
+    (Action::CopyUsername, &["Y"]),
+    (Action::CopyTotp, &["t"]),
+    (Action::SetTotp, &["T"]),
+    (Action::Reveal, &["v", "ctrl-v"]),
+    (Action::Edit, &["e"]),
+    (Action::NewEntry, &["o"]),
+    (Action::NewGroup, &["O"]),
+    (Action::Delete, &["d", "del"]),
+    (Action::Move, &["m"]),
+    (Action::Undo, &["u"]),
+    (Action::Redo, &["ctrl-r"]),
+];
+
+const EMACS_KEYS: [(Action, &[&str]); 19] = [
+    (Action::Quit, &["esc", "ctrl-q", "ctrl-c"]),
+    (Action::CommandPalette, &["alt-x", "ctrl-k"]),
+    (Action::Up, &["ctrl-p", "up"]),
+    (Action::Down, &["ctrl-n", "down"]),
+    (Action::Expand, &["ctrl-f", "right"]),
+    (Action::Collapse, &["ctrl-b", "left"]),
+    (Action::Top, &["alt-<", "home"]),
+    (Action::Bottom, &["alt->", "end"]),
+    (Action::PageUp, &["alt-v"]),
+    (Action::PageDown, &["ctrl-v"]),
+    (Action::Search, &["ctrl-s"]),
+    (Action::NextMatch, &["alt-n"]),
+    (Action::PreviousMatch, &["alt-p"]),
+    (Action::CopyPassword, &["alt-w"]),
+    (Action::CopyTotp, &["alt-o"]),
+    (Action::NewEntry, &["ctrl-o"]),
+    (Action::NewGroup, &["alt-g"]),
+    (Action::Delete, &["ctrl-d", "del"]),
+    (Action::Save, &["alt-s"]),
+];
+
+impl Action {
+    pub fn parse(name: &str) -> Option<Self> {
+        ACTIONS
+            .iter()
+            .find(|(_, n, _)| *n == name)
+            .map(|(a, _, _)| *a)
+    }
+
+    pub fn all() -> impl Iterator<Item = Action> {
+        ACTIONS.iter().map(|(a, _, _)| *a)
+    }
+
+    pub fn name(&self) -> &'static str {
+        ACTIONS
+            .iter()
+            .find(|(a, _, _)| a == self)
+            .map_or("", |(_, name, _)| name)
+    }
+
+    pub fn description(&self) -> &'static str {
+        ACTIONS
+            .iter()
+            .find(|(a, _, _)| a == self)
+            .map_or("", |(_, _, description)| description)
+    }
+}
+
+/// A key with its modifiers, e.g. `ctrl-p`, `alt-v`, `G` or `pgdn`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyChord {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl KeyChord {
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = text;
+        // a single `-` is the key itself
+        while let Some((modifier, key)) = rest.split_once('-').filter(|(_, k)| !k.is_empty()) {
+            modifiers |= match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "c" => KeyModifiers::CONTROL,
+                "alt" | "m" => KeyModifiers::ALT,
+                "shift" | "s" => KeyModifiers::SHIFT,
+                _ => return None,
+            };
+            rest = key;
+        }
+        let code = match rest.to_ascii_lowercase().as_str() {
+            "esc" | "escape" => KeyCode::Esc,
+            "enter" | "return" => KeyCode::Enter,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "backspace" | "bs" => KeyCode::Backspace,
+            "del" | "delete" => KeyCode::Delete,
+            "ins" | "insert" => KeyCode::Insert,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pgup" | "pageup" => KeyCode::PageUp,
+            "pgdn" | "pagedown" => KeyCode::PageDown,
+            "space" => KeyCode::Char(' '),
+            name if name.len() > 1 && name.starts_with('f') => {
+                KeyCode::F(name[1..].parse().ok().filter(|n| (1..=12).contains(n))?)
+            }
+            _ => {
+                let mut chars = rest.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => return None,
+                }
+            }
+        };
+        Some(Self::normalize(code, modifiers))
+    }
+
+    pub fn from_event(key: &KeyEvent) -> Self {
+        Self::normalize(key.code, key.modifiers)
+    }
+
+    /// Whether the chord types a character into a text input
+    pub fn is_text(&self) -> bool {
+        matches!(self.code, KeyCode::Char(_)) && self.modifiers.is_empty()
+    }
+
+    // the case of a letter tells about shift, terminals don't agree on reporting it
+    fn normalize(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        match code {
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::SHIFT) => KeyChord {
+                code: KeyCode::Char(c.to_ascii_uppercase()),
+                modifiers: modifiers - KeyModifiers::SHIFT,
+            },
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::CONTROL) => KeyChord {
+                code: KeyCode::Char(c.to_ascii_lowercase()),
+                modifiers,
+            },
+            KeyCode::Tab | KeyCode::BackTab
+                if code == KeyCode::BackTab || modifiers.contains(KeyModifiers::SHIFT) =>
+            {
+                KeyChord {
+                    code: KeyCode::BackTab,
+                    modifiers: modifiers - KeyModifiers::SHIFT,
+                }
+            }
+            _ => KeyChord { code, modifiers },
+        }
+    }
+}
+
+impl Display for KeyChord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut name = String::new();
+        for (modifier, prefix) in [
+            (KeyModifiers::CONTROL, "Ctrl-"),
+            (KeyModifiers::ALT, "Alt-"),
+            (KeyModifiers::SHIFT, "Shift-"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                name.push_str(prefix);
+            }
+        }
+        match self.code {
+            KeyCode::Char(' ') => name.push_str("Space"),
+            KeyCode::Char(c) => name.push(c),
+            KeyCode::F(n) => name.push_str(&format!("F{}", n)),
+            KeyCode::PageUp => name.push_str("PgUp"),
+            KeyCode::PageDown => name.push_str("PgDn"),
+            KeyCode::Delete => name.push_str("Del"),
+            code => name.push_str(&format!("{:?}", code)),
+        }
+        write!(f, "<{}>", name)
+    }
+}
+
+/// Key chords of every action, one chord triggers at most one action
+#[derive(Debug, Clone)]
+pub struct KeyMap {
+    bindings: Vec<(KeyChord, Action)>,
+}
+
+impl KeyMap {
+    /// One of [PRESETS], each builds on the default keys
+    pub fn preset(name: &str) -> Option<Self> {
+        let mut keys = KeyMap {
+            bindings: Vec::new(),
+        };
+        let preset: &[(Action, &[&str])] = match name {
+            "default" => &[],
+            "vim" => &VIM_KEYS,
+            "emacs" => &EMACS_KEYS,
+            _ => return None,
+        };
+        for (action, chords) in DEFAULT_KEYS.iter().chain(preset) {
+            keys.bind_names(*action, chords);
+        }
+        Some(keys)
+    }
+
+    /// Replace the chords of the action, other actions lose these chords
+    pub fn bind(&mut self, action: Action, chords: &[KeyChord]) {
+        self.bindings
+            .retain(|(chord, a)| *a != action && !chords.contains(chord));
+        self.bindings
+            .extend(chords.iter().map(|chord| (*chord, action)));
+    }
+
+    fn bind_names(&mut self, action: Action, names: &[&str]) {
+        let chords: Vec<KeyChord> = names.iter().filter_map(|n| KeyChord::parse(n)).collect();
+        self.bind(action, &chords);
+    }
+
+    pub fn action(&self, key: &KeyEvent) -> Option<Action> {
+        let chord = KeyChord::from_event(key);
+        self.bindings
+            .iter()
+            .find(|(c, _)| *c == chord)
+            .map(|(_, a)| *a)
+    }
+
+    /// Action of a key while a text input has the focus, typed characters are never actions
+    pub fn text_action(&self, key: &KeyEvent) -> Option<Action> {
+        match KeyChord::from_event(key) {
+            chord if chord.is_text() => None,
+            _ => self.action(key),
+        }
+    }
+
+    /// First chord of the action for help texts, e.g. `<Ctrl-p>`
+    pub fn key(&self, action: Action) -> Option<String> {
+        self.bindings
+            .iter()
+            .find(|(_, a)| *a == action)
+            .map(|(c, _)| c.to_string())
+    }
+
+    /// All chords of the action, e.g. `<:> <Ctrl-k>`
+    pub fn keys(&self, action: Action) -> String {
+        let keys: Vec<String> = self
+            .bindings
+            .iter()
+            .filter(|(_, a)| *a == action)
+            .map(|(c, _)| c.to_string())
+            .collect();
+        keys.join(" ")
+    }
+
+    /// First chord of the action that works inside a text input
+    pub fn text_key(&self, action: Action) -> Option<String> {
+        self.bindings
+            .iter()
+            .find(|(c, a)| *a == action && !c.is_text())
+            .map(|(c, _)| c.to_string())
+    }
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        Self::preset("default").unwrap_or(KeyMap {
+            bindings: Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chord(text: &str) -> KeyChord {
+        KeyChord::parse(text).unwrap_or_else(|| panic!("{} is not a key", text))
+    }
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn parse_keys_and_modifiers() {
+        assert_eq!(
+            chord("ctrl-p"),
+            KeyChord {
+                code: KeyCode::Char('p'),
+                modifiers: KeyModifiers::CONTROL
+            }
+        );
+        assert_eq!(chord("C-P"), chord("ctrl-p"));
+        assert_eq!(chord("m-v"), chord("alt-v"));
+        assert_eq!(chord("ctrl-alt-x"), chord("alt-ctrl-x"));
+        assert_eq!(chord("pgdn"), chord("PageDown"));
+        assert_eq!(chord("space").code, KeyCode::Char(' '));
+        assert_eq!(chord("f12").code, KeyCode::F(12));
+        // a single dash is the key itself, also after a modifier
+        assert_eq!(chord("-").code, KeyCode::Char('-'));
+        assert_eq!(chord("ctrl--").code, KeyCode::Char('-'));
+        for invalid in ["", "pp", "f0", "f13", "hyper-x", "ctrl-", "ctrl-enterx"] {
+            assert_eq!(KeyChord::parse(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn shift_is_told_by_the_case() {
+        assert_eq!(chord("shift-g"), chord("G"));
+        assert_eq!(chord("G").modifiers, KeyModifiers::NONE);
+        assert_eq!(chord("shift-tab"), chord("backtab"));
+        assert_eq!(chord("ctrl-P"), chord("ctrl-p"));
+        assert_ne!(chord("ctrl-shift-p"), chord("ctrl-p"));
+        assert_ne!(chord("g"), chord("G"));
+        // terminals report shift with the capital or only the capital
+        let capital = chord("G");
+        assert_eq!(
+            KeyChord::from_event(&key(KeyCode::Char('G'), KeyModifiers::SHIFT)),
+            capital
+        );
+        assert_eq!(
+            KeyChord::from_event(&key(KeyCode::Char('g'), KeyModifiers::SHIFT)),
+            capital
+        );
+        assert_eq!(
+            KeyChord::from_event(&key(KeyCode::Char('G'), KeyModifiers::NONE)),
+            capital
+        );
+        // control keeps the letter whatever case the terminal reports
+        assert_eq!(
+            KeyChord::from_event(&key(KeyCode::Char('P'), KeyModifiers::CONTROL)),
+            chord("ctrl-p")
+        );
+        assert_eq!(
+            KeyChord::from_event(&key(KeyCode::Tab, KeyModifiers::SHIFT)),
+            chord("backtab")
+        );
+    }
+
+    #[test]
+    fn display() {
+        for (text, shown) in [
+            ("ctrl-p", "<Ctrl-p>"),
+            ("alt-shift-x", "<Alt-X>"),
+            ("space", "<Space>"),
+            ("f5", "<F5>"),
+            ("pgup", "<PgUp>"),
+            ("del", "<Del>"),
+            ("enter", "<Enter>"),
+            (":", "<:>"),
+        ] {
+            assert_eq!(chord(text).to_string(), shown);
+        }
+        assert!(chord("G").is_text());
+        assert!(!chord("ctrl-g").is_text());
+        assert!(!chord("enter").is_text());
+    }
+
+    #[test]
+    fn presets_replace_default_keys() {
+        let default = KeyMap::default();
+        let vim = KeyMap::preset("vim").expect("the vim preset");
+        let esc = key(KeyCode::Esc, KeyModifiers::NONE);
+        let q = key(KeyCode::Char('q'), KeyModifiers::NONE);
+        assert_eq!(default.action(&esc), Some(Action::Quit));
+        assert_eq!(default.action(&q), None);
+        assert_eq!(vim.action(&esc), None);
+        assert_eq!(vim.action(&q), Some(Action::Quit));
+        // ctrl-r moves from reveal to redo
+        let ctrl_r = key(KeyCode::Char('r'), KeyModifiers::CONTROL);
+        assert_eq!(default.action(&ctrl_r), Some(Action::Reveal));
+        assert_eq!(vim.action(&ctrl_r), Some(Action::Redo));
+        assert_eq!(vim.keys(Action::Reveal), "<v> <Ctrl-v>");
+        // keys the preset leaves alone are the default ones
+        assert_eq!(vim.keys(Action::Save), default.keys(Action::Save));
+        assert!(KeyMap::preset("nano").is_none());
+    }
+
+    #[test]
+    fn a_chord_triggers_one_action() {
+        let mut keys = KeyMap::default();
+        keys.bind(Action::Edit, &[chord("ctrl-p"), chord("e")]);
+        let ctrl_p = key(KeyCode::Char('p'), KeyModifiers::CONTROL);
+        assert_eq!(keys.action(&ctrl_p), Some(Action::Edit));
+        assert_eq!(keys.key(Action::CopyPassword), None);
+        assert_eq!(keys.keys(Action::Edit), "<Ctrl-p> <e>");
+        // typed characters go to a text input
+        let e = key(KeyCode::Char('e'), KeyModifiers::NONE);
+        assert_eq!(keys.action(&e), Some(Action::Edit));
+        assert_eq!(keys.text_action(&e), None);
+        assert_eq!(keys.text_action(&ctrl_p), Some(Action::Edit));
+        assert_eq!(keys.text_key(Action::Edit), Some("<Ctrl-p>".to_string()));
+        keys.bind(Action::Edit, &[]);
+        assert_eq!(keys.action(&e), None);
+        assert_eq!(keys.keys(Action::Edit), "");
+    }
+
+    #[test]
+    fn action_names() {
+        for action in Action::all() {
+            assert_eq!(Action::parse(action.name()), Some(action));
+            assert!(!action.description().is_empty());
+        }
+        assert_eq!(Action::parse("copy-password"), Some(Action::CopyPassword));
+        assert_eq!(Action::parse("Copy-Password"), None);
+    }
+}
@@ -2,21 +2,46 @@
 #![feature(specialization)]
 extern crate core;
 
+use crate::cli::Invocation;
+use crate::config::Config;
 use crate::model::SafeModel;
-use crate::ui::view_models::run;
-use std::env;
+use std::{env, process};
 
+mod cli;
+mod config;
 mod contracts;
+mod keymap;
 mod model;
 mod ui;
 
-fn main() /* -> Result<(), io::Error> */
-{
-    let args = env::args();
-    if let Some(file) = args.last() {
-        let model = SafeModel::new(file.as_str());
-        if let Err(e) = run(model) {
-            panic!("{:?}", e);
+fn main() {
+    // broken settings are reported after --help and --version were handled
+    let config = Config::load();
+    let base = config.as_ref().cloned().unwrap_or_default();
+    let cli = match cli::parse(env::args().skip(1), base) {
+        Ok(Invocation::Run(cli)) => *cli,
+        Ok(Invocation::Help) => {
+            print!("{}", cli::USAGE);
+            return;
         }
+        Ok(Invocation::Version) => {
+            println!("tui-pwsafe {}", env!("CARGO_PKG_VERSION"));
+            return;
+        }
+        Err(e) => {
+            eprintln!(
+                "tui-pwsafe: {}\nTry 'tui-pwsafe --help' for more information.",
+                e
+            );
+            process::exit(2);
+        }
+    };
+    if let Err(e) = config {
+        eprintln!("tui-pwsafe: {}", e);
+        process::exit(e.exit_code());
+    }
+    if let Err(e) = cli::commands::execute(cli) {
+        eprintln!("tui-pwsafe: {}", e);
+        process::exit(e.exit_code());
     }
 }
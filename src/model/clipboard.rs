@@ -0,0 +1,70 @@
+use crate::contracts::TuiPwSafeErrors;
+use crate::contracts::TuiPwSafeErrors::ClipboardUnavailable;
+use crate::model::secret::Secret;
+use arboard::Clipboard;
+use std::time::{Duration, Instant};
+
+/// Remembers what was copied and clears the system clipboard when it expires,
+/// unless another application replaced the content in between
+pub struct ClipboardManager {
+    // created on first use, the command line works without a display
+    cl: Option<Clipboard>,
+    copied: Option<Secret<String>>,
+    clear_at: Option<Instant>,
+}
+
+impl ClipboardManager {
+    pub fn new() -> Self {
+        ClipboardManager {
+            cl: None,
+            copied: None,
+            clear_at: None,
+        }
+    }
+
+    /// Copy the content, a zero timeout keeps it in the clipboard
+    pub fn copy(&mut self, content: &str, timeout: Duration) -> Result<(), TuiPwSafeErrors> {
+        let cl = match &mut self.cl {
+            Some(cl) => cl,
+            None => self
+                .cl
+                .insert(Clipboard::new().map_err(|_| ClipboardUnavailable)?),
+        };
+        cl.set_text(content).map_err(|_| ClipboardUnavailable)?;
+        self.copied = Some(Secret::text(content.to_string()));
+        // a timeout beyond the representable instants never clears either
+        self.clear_at = Some(timeout)
+            .filter(|t| !t.is_zero())
+            .and_then(|t| Instant::now().checked_add(t));
+        Ok(())
+    }
+
+    /// Time until the copied value is cleared
+    pub fn remaining(&self) -> Option<Duration> {
+        self.clear_at
+            .map(|at| at.saturating_duration_since(Instant::now()))
+    }
+
+    /// Clear the clipboard once the timeout passed, called with every tick
+    pub fn expire(&mut self) {
+        if self.clear_at.is_some_and(|at| at <= Instant::now()) {
+            self.clear();
+        }
+    }
+
+    /// Clear the clipboard if it still holds the copied value
+    pub fn clear(&mut self) {
+        if let (Some(cl), Some(copied)) = (&mut self.cl, self.copied.take()) {
+            if cl.get_text().is_ok_and(|text| text == *copied.expose()) {
+                let _ = cl.clear();
+            }
+        }
+        self.clear_at = None;
+    }
+}
+
+impl Drop for ClipboardManager {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
@@ -0,0 +1,372 @@
+//! Passphrases of random words, from the bundled wordlist or a wordlist file of the user
+
+use crate::contracts::TuiPwSafeErrors;
+use crate::contracts::TuiPwSafeErrors::{InvalidPolicy, InvalidWordlist};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// 1296 words numbered by four dice like the short lists of the EFF, none is the start of another
+const BUNDLED: &str = include_str!("wordlist.txt");
+
+/// Most words and digits of a passphrase
+pub const MAX_WORDS: usize = 64;
+
+/// Capitalisation of the words, random adds a bit of entropy per word
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Capitalise {
+    None,
+    First,
+    All,
+    Random,
+}
+
+pub const CAPITALISE: [(Capitalise, &str); 4] = [
+    (Capitalise::None, "none"),
+    (Capitalise::First, "first"),
+    (Capitalise::All, "all"),
+    (Capitalise::Random, "random"),
+];
+
+/// Words to choose from, the lines of a diceware list with or without the dice numbers
+#[derive(Debug, Clone)]
+pub struct Wordlist {
+    // none for the bundled list
+    path: Option<PathBuf>,
+    words: Vec<String>,
+}
+
+/// Number of words, their separator and capitalisation, and the digits put after random words
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diceware {
+    pub words: usize,
+    pub separator: String,
+    pub capitalise: Capitalise,
+    pub digits: usize,
+}
+
+/// Six words of the bundled list are about 62 bits of entropy
+impl Default for Diceware {
+    fn default() -> Self {
+        Diceware {
+            words: 6,
+            separator: "-".to_string(),
+            capitalise: Capitalise::None,
+            digits: 0,
+        }
+    }
+}
+
+impl Capitalise {
+    pub fn parse(name: &str) -> Option<Self> {
+        CAPITALISE.iter().find(|(_, n)| *n == name).map(|(c, _)| *c)
+    }
+
+    pub fn name(&self) -> &'static str {
+        CAPITALISE
+            .iter()
+            .find(|(c, _)| c == self)
+            .map_or("none", |(_, n)| n)
+    }
+
+    /// The next or the previous way, wrapping around
+    pub fn cycle(&self, forward: bool) -> Self {
+        let at = CAPITALISE.iter().position(|(c, _)| c == self).unwrap_or(0);
+        let step = if forward { 1 } else { CAPITALISE.len() - 1 };
+        CAPITALISE[(at + step) % CAPITALISE.len()].0
+    }
+}
+
+impl Wordlist {
+    pub fn bundled() -> Self {
+        Self::parse(BUNDLED, None)
+    }
+
+    /// The wordlist file if one is given, otherwise the bundled list
+    pub fn load(path: Option<&Path>) -> Result<Self, TuiPwSafeErrors> {
+        let Some(path) = path else {
+            return Ok(Self::bundled());
+        };
+        let invalid = |e: String| InvalidWordlist(format!("{}: {}", path.display(), e));
+        let text = fs::read_to_string(path).map_err(|e| invalid(e.to_string()))?;
+        let wordlist = Self::parse(&text, Some(path.to_path_buf()));
+        if wordlist.words.len() < 2 {
+            return Err(invalid("it needs at least two different words".to_string()));
+        }
+        Ok(wordlist)
+    }
+
+    /// The last word of every line, so that dice numbers are skipped, `#` starts a comment
+    fn parse(text: &str, path: Option<PathBuf>) -> Self {
+        let mut words: Vec<String> = text
+            .lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .filter_map(|line| line.split_whitespace().last())
+            .map(str::to_string)
+            .collect();
+        words.sort();
+        words.dedup();
+        Wordlist { path, words }
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    /// Where the words are from, e.g. `bundled, 1296 words`
+    pub fn describe(&self) -> String {
+        match &self.path {
+            Some(path) => format!("{}, {} words", path.display(), self.len()),
+            None => format!("bundled, {} words", self.len()),
+        }
+    }
+}
+
+impl Diceware {
+    /// Whether passphrases can be made with these settings
+    pub fn check(&self) -> Result<(), TuiPwSafeErrors> {
+        if self.words == 0 || self.words > MAX_WORDS {
+            return Err(InvalidPolicy(format!(
+                "the number of words has to be between 1 and {}",
+                MAX_WORDS
+            )));
+        }
+        if self.digits > MAX_WORDS {
+            return Err(InvalidPolicy(format!(
+                "at most {} digits can be added",
+                MAX_WORDS
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn generate(&self, wordlist: &Wordlist) -> Result<String, TuiPwSafeErrors> {
+        self.check()?;
+        let mut rng = rand::thread_rng();
+        let mut words: Vec<String> = (0..self.words)
+            .map(|i| {
+                let word = wordlist.words[rng.gen_range(0..wordlist.len())].clone();
+                let upper = match self.capitalise {
+                    Capitalise::None => false,
+                    Capitalise::First => i == 0,
+                    Capitalise::All => true,
+                    Capitalise::Random => rng.gen_bool(0.5),
+                };
+                if upper {
+                    capitalised(&word)
+                } else {
+                    word
+                }
+            })
+            .collect();
+        for _ in 0..self.digits {
+            let digit = char::from_digit(rng.gen_range(0..10), 10).unwrap_or('0');
+            if let Some(word) = words.choose_mut(&mut rng) {
+                word.push(digit);
+            }
+        }
+        Ok(words.join(&self.separator))
+    }
+
+    /// Bits of entropy of a passphrase, the separator adds none
+    pub fn entropy(&self, wordlist: &Wordlist) -> f64 {
+        if self.check().is_err() {
+            return 0.0;
+        }
+        let words = self.words as f64;
+        let case = if self.capitalise == Capitalise::Random {
+            1.0
+        } else {
+            0.0
+        };
+        // a digit and the word it follows, an upper bound as the order of the digits is lost
+        let digit = 10f64.log2() + words.log2();
+        words * ((wordlist.len() as f64).log2() + case) + self.digits as f64 * digit
+    }
+}
+
+fn capitalised(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    /// A wordlist file of the test, removed when dropped
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, content: &[u8]) -> Self {
+            let path = env::temp_dir().join(format!("tui-pwsafe-{}-{}", process::id(), name));
+            fs::write(&path, content).expect("a writable temp dir");
+            TempFile(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn words(text: &str) -> Wordlist {
+        Wordlist::parse(text, None)
+    }
+
+    fn generate(diceware: &Diceware, wordlist: &Wordlist) -> String {
+        diceware.generate(wordlist).ok().expect("valid settings")
+    }
+
+    #[test]
+    fn bundled_wordlist() {
+        let wordlist = Wordlist::bundled();
+        assert_eq!(wordlist.len(), 1296);
+        assert_eq!(wordlist.describe(), "bundled, 1296 words");
+        assert!(wordlist
+            .words
+            .iter()
+            .all(|word| word.chars().all(|c| c.is_ascii_lowercase())));
+        assert!(Wordlist::load(None).is_ok_and(|w| w.len() == 1296));
+    }
+
+    #[test]
+    fn dice_numbers_comments_and_repeats_are_skipped() {
+        let wordlist = words("# a comment\n11\tapple\nbanana\n\n  # indented\n12 apple\n");
+        assert_eq!(wordlist.words, ["apple", "banana"]);
+    }
+
+    #[test]
+    fn wordlist_file() {
+        let file = TempFile::new("words.txt", b"1 cherry\n2 plum\n3 pear\n");
+        let wordlist = Wordlist::load(Some(&file.0)).ok().expect("three words");
+        assert_eq!(wordlist.words, ["cherry", "pear", "plum"]);
+        assert_eq!(
+            wordlist.describe(),
+            format!("{}, 3 words", file.0.display())
+        );
+    }
+
+    #[test]
+    fn bad_wordlist_files() {
+        let missing = env::temp_dir().join(format!("tui-pwsafe-{}-missing", process::id()));
+        let one_word = TempFile::new("one-word.txt", b"1 plum\n2 plum\n# pear\n");
+        let binary = TempFile::new("binary.txt", b"plum\n\xff\xfe\n");
+        for path in [&missing, &one_word.0, &binary.0] {
+            match Wordlist::load(Some(path)) {
+                Err(InvalidWordlist(problem)) => {
+                    assert!(problem.starts_with(&path.display().to_string()))
+                }
+                _ => panic!("{} was loaded", path.display()),
+            }
+        }
+    }
+
+    #[test]
+    fn words_and_separator() {
+        let wordlist = words("apple\nbanana\ncherry\n");
+        let diceware = Diceware {
+            words: 5,
+            separator: " + ".to_string(),
+            ..Diceware::default()
+        };
+        for _ in 0..20 {
+            let passphrase = generate(&diceware, &wordlist);
+            let words: Vec<&str> = passphrase.split(" + ").collect();
+            assert_eq!(words.len(), 5, "{}", passphrase);
+            assert!(words.iter().all(|w| wordlist.words.iter().any(|l| l == w)));
+        }
+        let joined = Diceware {
+            separator: String::new(),
+            ..diceware
+        };
+        assert!(generate(&joined, &wordlist)
+            .chars()
+            .all(char::is_alphabetic));
+    }
+
+    #[test]
+    fn capitalisation() {
+        let wordlist = words("apple\nbanana\n");
+        let words = |capitalise| {
+            let diceware = Diceware {
+                words: 4,
+                capitalise,
+                ..Diceware::default()
+            };
+            generate(&diceware, &wordlist)
+                .split('-')
+                .map(|w| w.starts_with(|c: char| c.is_uppercase()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(words(Capitalise::None), [false; 4]);
+        assert_eq!(words(Capitalise::First), [true, false, false, false]);
+        assert_eq!(words(Capitalise::All), [true; 4]);
+        assert!((0..50).any(|_| words(Capitalise::Random) != [false; 4]));
+        assert_eq!(Capitalise::parse("random"), Some(Capitalise::Random));
+        assert_eq!(Capitalise::parse("Random"), None);
+        assert_eq!(Capitalise::Random.cycle(true), Capitalise::None);
+        assert_eq!(Capitalise::None.cycle(false), Capitalise::Random);
+    }
+
+    #[test]
+    fn digits_follow_words() {
+        let wordlist = words("apple\nbanana\n");
+        let diceware = Diceware {
+            words: 3,
+            digits: 4,
+            ..Diceware::default()
+        };
+        for _ in 0..20 {
+            let passphrase = generate(&diceware, &wordlist);
+            let digits = passphrase.chars().filter(char::is_ascii_digit).count();
+            assert_eq!(digits, 4, "{}", passphrase);
+            for word in passphrase.split('-') {
+                let letters = word.trim_end_matches(|c: char| c.is_ascii_digit());
+                assert!(
+                    wordlist.words.iter().any(|l| l == letters),
+                    "{}",
+                    passphrase
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn limits_and_entropy() {
+        let wordlist = Wordlist::bundled();
+        for diceware in [
+            Diceware {
+                words: 0,
+                ..Diceware::default()
+            },
+            Diceware {
+                words: MAX_WORDS + 1,
+                ..Diceware::default()
+            },
+            Diceware {
+                digits: MAX_WORDS + 1,
+                ..Diceware::default()
+            },
+        ] {
+            assert!(matches!(
+                diceware.generate(&wordlist),
+                Err(InvalidPolicy(_))
+            ));
+            assert_eq!(diceware.entropy(&wordlist), 0.0);
+        }
+        let default = Diceware::default();
+        assert!((default.entropy(&wordlist) - 6.0 * 1296f64.log2()).abs() < 1e-9);
+        let random = Diceware {
+            capitalise: Capitalise::Random,
+            ..Diceware::default()
+        };
+        assert!((random.entropy(&wordlist) - default.entropy(&wordlist) - 6.0).abs() < 1e-9);
+    }
+}
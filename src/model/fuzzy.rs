@@ -0,0 +1,136 @@
+//! Case insensitive subsequence matching with a score for ranking
+
+// bonus for a character directly following the previous match
+const CONSECUTIVE: i64 = 15;
+// bonus for a match at the start of the text or of a word
+const WORD_START: i64 = 10;
+const FIRST_CHAR: i64 = 5;
+// penalty per skipped character, capped so long texts are not ruled out
+const GAP: i64 = 1;
+const MAX_GAP_PENALTY: i64 = 10;
+
+/// Score of a match and the char positions of the matched characters in the text
+#[derive(Debug, Clone)]
+pub struct Match {
+    pub score: i64,
+    pub positions: Vec<usize>,
+}
+
+/// Match all characters of the pattern in order, the best of all start positions wins
+pub fn fuzzy_match(pattern: &str, text: &str) -> Option<Match> {
+    let pattern: Vec<char> = pattern.chars().flat_map(char::to_lowercase).collect();
+    let chars: Vec<char> = text.chars().collect();
+    let lower: Vec<char> = chars
+        .iter()
+        .map(|c| c.to_lowercase().next().unwrap_or(*c))
+        .collect();
+    let first = *pattern.first()?;
+
+    let mut best: Option<Match> = None;
+    for start in (0..lower.len()).filter(|&i| lower[i] == first) {
+        let mut positions = vec![start];
+        let mut next = 1;
+        for (i, c) in lower.iter().enumerate().skip(start + 1) {
+            if next == pattern.len() {
+                break;
+            }
+            if *c == pattern[next] {
+                positions.push(i);
+                next += 1;
+            }
+        }
+        if next < pattern.len() {
+            // later starts can not match either
+            break;
+        }
+        let score = score(&chars, &positions);
+        if best.as_ref().is_none_or(|b| score > b.score) {
+            best = Some(Match { score, positions });
+        }
+    }
+    best
+}
+
+fn score(chars: &[char], positions: &[usize]) -> i64 {
+    let mut score = 0;
+    for (n, &pos) in positions.iter().enumerate() {
+        if pos == 0 {
+            score += FIRST_CHAR;
+        }
+        if is_word_start(chars, pos) {
+            score += WORD_START;
+        }
+        if n > 0 {
+            let gap = (pos - positions[n - 1] - 1) as i64;
+            score += if gap == 0 {
+                CONSECUTIVE
+            } else {
+                -(gap * GAP).min(MAX_GAP_PENALTY)
+            };
+        }
+    }
+    score
+}
+
+fn is_word_start(chars: &[char], pos: usize) -> bool {
+    match pos.checked_sub(1).map(|p| chars[p]) {
+        None => true,
+        Some(prev) => !prev.is_alphanumeric() || (prev.is_lowercase() && chars[pos].is_uppercase()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(pattern: &str, text: &str) -> Option<Vec<usize>> {
+        fuzzy_match(pattern, text).map(|m| m.positions)
+    }
+
+    #[test]
+    fn characters_in_order() {
+        assert_eq!(positions("gm", "GitHub mail"), Some(vec![0, 7]));
+        assert_eq!(positions("mail", "GitHub mail"), Some(vec![7, 8, 9, 10]));
+        assert_eq!(positions("mg", "GitHub mail"), None);
+        assert_eq!(positions("gmx", "GitHub mail"), None);
+        assert_eq!(positions("", "GitHub mail"), None);
+        assert_eq!(positions("g", ""), None);
+    }
+
+    #[test]
+    fn case_is_ignored_on_both_sides() {
+        assert_eq!(positions("GM", "github mail"), Some(vec![0, 7]));
+        assert_eq!(positions("Mail", "MAIL"), Some(vec![0, 1, 2, 3]));
+        assert_eq!(positions("ÄB", "äb"), Some(vec![0, 1]));
+    }
+
+    #[test]
+    fn positions_count_chars_not_bytes() {
+        assert_eq!(positions("b", "äb"), Some(vec![1]));
+        assert_eq!(positions("ce", "Café en"), Some(vec![0, 5]));
+    }
+
+    #[test]
+    fn the_best_start_wins() {
+        // starting at the first a leaves a gap, the later a is followed by b
+        assert_eq!(positions("ab", "a_xab"), Some(vec![3, 4]));
+    }
+
+    #[test]
+    fn ranking() {
+        let score = |pattern, text| fuzzy_match(pattern, text).map(|m| m.score);
+        // word starts rank above matches inside words
+        assert!(score("gm", "GitHub mail") > score("gm", "program"));
+        // a lowercase letter followed by a capital starts a word
+        assert!(score("pm", "passwordManager") > score("pm", "passwordmanager"));
+        // consecutive characters rank above scattered ones
+        assert!(score("mail", "mail") > score("mail", "my aunt is lovely"));
+        // the start of the text ranks above a later word
+        assert!(score("bank", "bank") > score("bank", "my bank"));
+        // the gap penalty is capped, a long gap still matches
+        assert_eq!(
+            score("ab", &format!("a{}b", "x".repeat(100))),
+            score("ab", &format!("a{}b", "x".repeat(20)))
+        );
+    }
+}
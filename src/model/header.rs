@@ -0,0 +1,92 @@
+use crate::model::record::{int16, int32, text};
+use uuid::Uuid;
+
+/// Typed header fields as defined in section 3.3 of the Password Safe v3 format
+#[derive(Debug, PartialEq, Clone)]
+pub enum HeaderField {
+    Version(u16),
+    Uuid(Uuid),
+    NonDefaultPreferences(String),
+    TreeDisplayStatus(String),
+    TimestampLastSaved(u32),
+    WhatLastSaved(String),
+    LastSavedByUser(String),
+    LastSavedOnHost(String),
+    DatabaseName(String),
+    DatabaseDescription(String),
+    RecentlyUsedEntries(String),
+    NamedPasswordPolicies(String),
+    EmptyGroups(String),
+    LastMasterPasswordChange(u32),
+    // deprecated, reserved and implementation specific fields are kept as they are
+    Unknown(u8, Vec<u8>),
+}
+
+impl HeaderField {
+    pub fn from_raw(kind: u8, bytes: &[u8]) -> Self {
+        // the same as record fields, text that is not UTF-8 is kept as bytes
+        Self::parse(kind, bytes).unwrap_or_else(|| HeaderField::Unknown(kind, bytes.to_vec()))
+    }
+
+    fn parse(kind: u8, bytes: &[u8]) -> Option<Self> {
+        use HeaderField::*;
+        Some(match kind {
+            0x00 if bytes.len() >= 2 => Version(int16(bytes)),
+            0x01 if bytes.len() == 16 => Uuid(uuid::Uuid::from_slice(bytes).unwrap_or_default()),
+            0x02 => NonDefaultPreferences(text(bytes)?),
+            0x03 => TreeDisplayStatus(text(bytes)?),
+            0x04 if bytes.len() >= 4 => TimestampLastSaved(int32(bytes)),
+            0x06 => WhatLastSaved(text(bytes)?),
+            0x07 => LastSavedByUser(text(bytes)?),
+            0x08 => LastSavedOnHost(text(bytes)?),
+            0x09 => DatabaseName(text(bytes)?),
+            0x0a => DatabaseDescription(text(bytes)?),
+            0x0f => RecentlyUsedEntries(text(bytes)?),
+            0x10 => NamedPasswordPolicies(text(bytes)?),
+            0x11 => EmptyGroups(text(bytes)?),
+            0x13 if bytes.len() >= 4 => LastMasterPasswordChange(int32(bytes)),
+            _ => return None,
+        })
+    }
+
+    pub fn kind(&self) -> u8 {
+        use HeaderField::*;
+        match self {
+            Version(_) => 0x00,
+            Uuid(_) => 0x01,
+            NonDefaultPreferences(_) => 0x02,
+            TreeDisplayStatus(_) => 0x03,
+            TimestampLastSaved(_) => 0x04,
+            WhatLastSaved(_) => 0x06,
+            LastSavedByUser(_) => 0x07,
+            LastSavedOnHost(_) => 0x08,
+            DatabaseName(_) => 0x09,
+            DatabaseDescription(_) => 0x0a,
+            RecentlyUsedEntries(_) => 0x0f,
+            NamedPasswordPolicies(_) => 0x10,
+            EmptyGroups(_) => 0x11,
+            LastMasterPasswordChange(_) => 0x13,
+            Unknown(kind, _) => *kind,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        use HeaderField::*;
+        match self {
+            Version(v) => v.to_le_bytes().to_vec(),
+            Uuid(u) => u.as_bytes().to_vec(),
+            NonDefaultPreferences(s)
+            | TreeDisplayStatus(s)
+            | WhatLastSaved(s)
+            | LastSavedByUser(s)
+            | LastSavedOnHost(s)
+            | DatabaseName(s)
+            | DatabaseDescription(s)
+            | RecentlyUsedEntries(s)
+            | NamedPasswordPolicies(s)
+            | EmptyGroups(s) => s.as_bytes().to_vec(),
+            TimestampLastSaved(t) | LastMasterPasswordChange(t) => t.to_le_bytes().to_vec(),
+            Unknown(_, bytes) => bytes.clone(),
+        }
+    }
+}
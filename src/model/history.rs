@@ -0,0 +1,298 @@
+use crate::model::header::HeaderField;
+use crate::model::pwsfile::PwDb;
+use crate::model::record::DbRecord;
+use uuid::Uuid;
+
+/// A reversible change of the database, records move between the edit and the database
+pub enum Edit {
+    // the version of the record that is not in the database, swapped on apply and revert;
+    // filled_group is an empty group the record was moved into
+    Update {
+        record: DbRecord,
+        filled_group: Option<String>,
+    },
+    // the record is held by the edit while it is undone
+    Add {
+        uuid: Option<Uuid>,
+        record: DbRecord,
+        filled_group: Option<String>,
+    },
+    // the record is held by the edit while it is done
+    Remove {
+        index: usize,
+        uuid: Option<Uuid>,
+        record: DbRecord,
+    },
+    AddGroup(String),
+}
+
+/// Undo and redo stacks of all edits since the last save
+#[derive(Default)]
+pub struct History {
+    done: Vec<Edit>,
+    undone: Vec<Edit>,
+}
+
+impl Edit {
+    fn apply(&mut self, db: &mut PwDb) {
+        match self {
+            Edit::Update {
+                record,
+                filled_group,
+            } => {
+                swap(db, record);
+                remove_empty_group(db, filled_group);
+            }
+            Edit::Add {
+                record,
+                filled_group,
+                ..
+            } => {
+                db.records.push(std::mem::take(record));
+                remove_empty_group(db, filled_group);
+            }
+            Edit::Remove {
+                index,
+                uuid,
+                record,
+            } => {
+                if let Some(i) = position(db, *uuid) {
+                    *index = i;
+                    *record = db.records.remove(i);
+                }
+            }
+            Edit::AddGroup(name) => db.header.push(HeaderField::EmptyGroups(name.clone())),
+        }
+    }
+
+    fn revert(&mut self, db: &mut PwDb) {
+        match self {
+            Edit::Update {
+                record,
+                filled_group,
+            } => {
+                swap(db, record);
+                add_empty_group(db, filled_group);
+            }
+            Edit::Add {
+                uuid,
+                record,
+                filled_group,
+            } => {
+                if let Some(i) = position(db, *uuid) {
+                    *record = db.records.remove(i);
+                }
+                add_empty_group(db, filled_group);
+            }
+            Edit::Remove { index, record, .. } => {
+                let index = (*index).min(db.records.len());
+                db.records.insert(index, std::mem::take(record));
+            }
+            Edit::AddGroup(name) => db
+                .header
+                .retain(|h| !matches!(h, HeaderField::EmptyGroups(g) if g == name)),
+        }
+    }
+
+    /// The record the edit is about, used to select it again after undo and redo
+    pub fn uuid(&self) -> Option<Uuid> {
+        match self {
+            Edit::Update { record, .. } => record.uuid(),
+            Edit::Add { uuid, .. } | Edit::Remove { uuid, .. } => *uuid,
+            Edit::AddGroup(_) => None,
+        }
+    }
+}
+
+impl History {
+    /// Apply a new edit, this drops everything that was undone before
+    pub fn push(&mut self, mut edit: Edit, db: &mut PwDb) {
+        edit.apply(db);
+        self.done.push(edit);
+        self.undone.clear();
+    }
+
+    pub fn undo(&mut self, db: &mut PwDb) -> Option<&Edit> {
+        let mut edit = self.done.pop()?;
+        edit.revert(db);
+        self.undone.push(edit);
+        self.undone.last()
+    }
+
+    pub fn redo(&mut self, db: &mut PwDb) -> Option<&Edit> {
+        let mut edit = self.undone.pop()?;
+        edit.apply(db);
+        self.done.push(edit);
+        self.done.last()
+    }
+
+    pub fn clear(&mut self) {
+        self.done.clear();
+        self.undone.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.done.is_empty()
+    }
+}
+
+fn position(db: &PwDb, uuid: Option<Uuid>) -> Option<usize> {
+    db.records
+        .iter()
+        .position(|r| uuid.is_some() && r.uuid() == uuid)
+}
+
+fn swap(db: &mut PwDb, record: &mut DbRecord) {
+    if let Some(i) = position(db, record.uuid()) {
+        std::mem::swap(&mut db.records[i], record);
+    }
+}
+
+fn remove_empty_group(db: &mut PwDb, group: &Option<String>) {
+    if let Some(group) = group {
+        db.header
+            .retain(|h| !matches!(h, HeaderField::EmptyGroups(g) if g == group));
+    }
+}
+
+fn add_empty_group(db: &mut PwDb, group: &Option<String>) {
+    if let Some(group) = group {
+        db.header.push(HeaderField::EmptyGroups(group.clone()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::record::RecordField;
+
+    fn record(title: &str, group: &str) -> DbRecord {
+        let mut record = DbRecord::default();
+        record.set(RecordField::Uuid(Uuid::new_v4()));
+        record.set(RecordField::Title(title.to_string()));
+        record.set(RecordField::Group(group.to_string()));
+        record
+    }
+
+    fn db(titles: &[&str]) -> PwDb {
+        PwDb {
+            header: Vec::new(),
+            records: titles.iter().map(|t| record(t, "G")).collect(),
+        }
+    }
+
+    fn titles(db: &PwDb) -> Vec<&str> {
+        db.records.iter().filter_map(DbRecord::title).collect()
+    }
+
+    fn empty_groups(db: &PwDb) -> Vec<&str> {
+        db.header
+            .iter()
+            .filter_map(|h| match h {
+                HeaderField::EmptyGroups(g) => Some(g.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn uuid_of(db: &PwDb, i: usize) -> Option<Uuid> {
+        db.records[i].uuid()
+    }
+
+    #[test]
+    fn add() {
+        let mut db = db(&["a"]);
+        db.header.push(HeaderField::EmptyGroups("G".to_string()));
+        let mut history = History::default();
+        let record = record("b", "G");
+        let uuid = record.uuid();
+        let edit = Edit::Add {
+            uuid,
+            record,
+            filled_group: Some("G".to_string()),
+        };
+        history.push(edit, &mut db);
+        assert_eq!(titles(&db), ["a", "b"]);
+        assert!(empty_groups(&db).is_empty());
+        assert_eq!(history.undo(&mut db).and_then(Edit::uuid), uuid);
+        assert_eq!(titles(&db), ["a"]);
+        assert_eq!(empty_groups(&db), ["G"]);
+        assert_eq!(history.redo(&mut db).and_then(Edit::uuid), uuid);
+        assert_eq!(titles(&db), ["a", "b"]);
+        assert_eq!(uuid_of(&db, 1), uuid);
+        assert!(empty_groups(&db).is_empty());
+    }
+
+    #[test]
+    fn update() {
+        let mut db = db(&["a", "b"]);
+        let mut history = History::default();
+        let uuid = uuid_of(&db, 1);
+        let mut changed = record("c", "G");
+        changed.set(RecordField::Uuid(uuid.unwrap()));
+        let edit = Edit::Update {
+            record: changed,
+            filled_group: None,
+        };
+        history.push(edit, &mut db);
+        assert_eq!(titles(&db), ["a", "c"]);
+        assert_eq!(history.undo(&mut db).and_then(Edit::uuid), uuid);
+        assert_eq!(titles(&db), ["a", "b"]);
+        assert_eq!(history.redo(&mut db).and_then(Edit::uuid), uuid);
+        assert_eq!(titles(&db), ["a", "c"]);
+        assert_eq!(uuid_of(&db, 1), uuid);
+    }
+
+    #[test]
+    fn remove() {
+        let mut db = db(&["a", "b", "c"]);
+        let mut history = History::default();
+        let uuid = uuid_of(&db, 1);
+        let edit = Edit::Remove {
+            index: 1,
+            uuid,
+            record: DbRecord::default(),
+        };
+        history.push(edit, &mut db);
+        assert_eq!(titles(&db), ["a", "c"]);
+        // the record comes back at its place
+        assert_eq!(history.undo(&mut db).and_then(Edit::uuid), uuid);
+        assert_eq!(titles(&db), ["a", "b", "c"]);
+        assert_eq!(uuid_of(&db, 1), uuid);
+        assert_eq!(history.redo(&mut db).and_then(Edit::uuid), uuid);
+        assert_eq!(titles(&db), ["a", "c"]);
+        assert!(history.undo(&mut db).is_some());
+        assert_eq!(titles(&db), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn add_group() {
+        let mut db = db(&[]);
+        let mut history = History::default();
+        history.push(Edit::AddGroup("Work".to_string()), &mut db);
+        assert_eq!(empty_groups(&db), ["Work"]);
+        assert_eq!(history.undo(&mut db).map(Edit::uuid), Some(None));
+        assert!(empty_groups(&db).is_empty());
+        assert!(history.redo(&mut db).is_some());
+        assert_eq!(empty_groups(&db), ["Work"]);
+    }
+
+    #[test]
+    fn a_new_edit_drops_the_undone_ones() {
+        let mut db = db(&[]);
+        let mut history = History::default();
+        assert!(history.is_empty());
+        assert!(history.undo(&mut db).is_none());
+        history.push(Edit::AddGroup("a".to_string()), &mut db);
+        history.push(Edit::AddGroup("b".to_string()), &mut db);
+        assert!(history.undo(&mut db).is_some());
+        history.push(Edit::AddGroup("c".to_string()), &mut db);
+        assert!(history.redo(&mut db).is_none());
+        assert_eq!(empty_groups(&db), ["a", "c"]);
+        assert!(history.undo(&mut db).is_some());
+        assert!(history.undo(&mut db).is_some());
+        assert!(history.undo(&mut db).is_none());
+        assert!(history.is_empty());
+        assert!(empty_groups(&db).is_empty());
+    }
+}
@@ -1,40 +1,612 @@
 use crate::contracts::TuiPwSafeErrors;
-use rs_pwsafe::pwsdb::record::DbRecord;
-use rs_pwsafe::PwFile;
+use crate::model::clipboard::ClipboardManager;
+use crate::model::header::HeaderField;
+use crate::model::history::{Edit, History};
+use crate::model::policy::PasswordPolicy;
+use crate::model::pwsfile::{PwDb, SafeKeys};
+use crate::model::record::{DbRecord, RecordField};
+use crate::model::search::SearchHit;
+use crate::model::secret::Secret;
+use crate::model::totp::Totp;
 use std::collections::HashSet;
-use arboard::Clipboard;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{env, fs, io, process};
+use uuid::Uuid;
+
+pub mod clipboard;
+pub mod diceware;
+pub mod fuzzy;
+pub mod header;
+pub mod history;
+pub mod policy;
+pub mod pwsfile;
+pub mod record;
+pub mod search;
+pub mod secret;
+pub mod totp;
+
+/// Format version written to the header, 3.13
+const FORMAT_VERSION: u16 = 0x030d;
+const APP_NAME: &str = concat!("tui-pwsafe V", env!("CARGO_PKG_VERSION"));
 
 pub struct SafeModel {
-    pf: PwFile,
-    cl: Clipboard,
+    path: PathBuf,
+    data: Vec<u8>,
+    // unsaved changes encrypted when the safe was locked, unlocked instead of the file
+    locked_changes: Option<Vec<u8>>,
+    // the records hold changes from before the lock that are not saved yet
+    restored_changes: bool,
+    keys: Option<SafeKeys>,
+    db: PwDb,
+    history: History,
+    // cleared when the model is dropped, so quitting clears it as well
+    clipboard: ClipboardManager,
 }
 
 impl SafeModel {
-    pub fn new(path: &str) -> Self {
-        let safe = match PwFile::open(path) {
-            Ok(f) => f,
-            Err(e) => panic!("{}", TuiPwSafeErrors::from(e)),
-        };
-        let clipboard = Clipboard::new().expect("Can't create clipboard");
-        SafeModel { pf: safe, cl:clipboard }
+    pub fn new(path: &str) -> Result<Self, TuiPwSafeErrors> {
+        let data = Self::read(path)?;
+        Ok(SafeModel {
+            path: PathBuf::from(path),
+            data,
+            locked_changes: None,
+            restored_changes: false,
+            keys: None,
+            db: PwDb::default(),
+            history: History::default(),
+            clipboard: ClipboardManager::new(),
+        })
+    }
+
+    /// Model for a safe that does not exist yet, see [SafeModel::initialize]
+    pub fn create(path: &str) -> Result<Self, TuiPwSafeErrors> {
+        if Path::new(path).exists() {
+            return Err(TuiPwSafeErrors::StoreFileExists);
+        }
+        Ok(SafeModel {
+            path: PathBuf::from(path),
+            data: Vec::new(),
+            locked_changes: None,
+            restored_changes: false,
+            keys: None,
+            db: PwDb::default(),
+            history: History::default(),
+            clipboard: ClipboardManager::new(),
+        })
+    }
+
+    fn read(path: &str) -> Result<Vec<u8>, TuiPwSafeErrors> {
+        // only a missing safe is reported as such, other missing files are I/O errors
+        let data = fs::read(path).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => TuiPwSafeErrors::StoreFileNotFound,
+            _ => TuiPwSafeErrors::IoError(e),
+        })?;
+        pwsfile::check_format(&data)?;
+        Ok(data)
     }
 
-    pub fn to_clipboard(&mut self, content: &str) {
-        self.cl.set_text(content.to_string()).expect("can't copy to clipboard");
+    /// Copy to the system clipboard, it is cleared again after the timeout
+    pub fn set_clipboard(
+        &mut self,
+        content: &str,
+        timeout: Duration,
+    ) -> Result<(), TuiPwSafeErrors> {
+        self.clipboard.copy(content, timeout)
     }
-    
+
+    pub fn clipboard_remaining(&self) -> Option<Duration> {
+        self.clipboard.remaining()
+    }
+
+    pub fn expire_clipboard(&mut self) {
+        self.clipboard.expire();
+    }
+
+    /// Decrypt the records, or the unsaved changes kept when the safe was locked
     pub fn unlock(&mut self, phrase: &str) -> Result<(), TuiPwSafeErrors> {
-        match self.pf.unlock(phrase) {
-            Err(e) => Err(TuiPwSafeErrors::from(e)),
-            Ok(_) => Ok(()),
+        let data = self.locked_changes.as_ref().unwrap_or(&self.data);
+        let (keys, mut db) = pwsfile::decrypt(data, phrase)?;
+        // records are addressed by uuid, pwsafe assigns missing ones the same way
+        for record in db.records.iter_mut().filter(|r| r.uuid().is_none()) {
+            record.set(RecordField::Uuid(Uuid::new_v4()));
+        }
+        self.keys = Some(keys);
+        self.db = db;
+        self.history.clear();
+        self.restored_changes = self.locked_changes.take().is_some();
+        Ok(())
+    }
+
+    pub fn is_unlocked(&self) -> bool {
+        self.keys.is_some()
+    }
+
+    /// Forget keys and records, their memory is wiped when dropped.
+    /// Kept unsaved changes stay encrypted in memory until the next unlock
+    pub fn lock(&mut self, keep_changes: bool) {
+        let Some(keys) = &self.keys else {
+            return;
+        };
+        self.locked_changes =
+            (keep_changes && self.is_dirty()).then(|| pwsfile::encrypt(&self.db, keys));
+        self.keys = None;
+        self.db = PwDb::default();
+        self.history.clear();
+        self.restored_changes = false;
+        self.clipboard.clear();
+    }
+
+    /// Whether the locked safe keeps unsaved changes, they can be saved after unlocking
+    pub fn has_locked_changes(&self) -> bool {
+        self.locked_changes.is_some()
+    }
+
+    pub fn is_new(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Write an empty safe protected by the phrase and keep it unlocked
+    pub fn initialize(&mut self, phrase: &str) -> Result<(), TuiPwSafeErrors> {
+        if !self.is_new() {
+            return Err(TuiPwSafeErrors::StoreFileExists);
+        }
+        self.keys = Some(SafeKeys::generate(phrase, pwsfile::DEFAULT_ITERATIONS));
+        self.db = PwDb::default();
+        self.db
+            .set_header(HeaderField::LastMasterPasswordChange(now()));
+        self.save()
+    }
+
+    /// Key stretch iterations of the unlocked safe
+    pub fn iterations(&self) -> u32 {
+        match &self.keys {
+            Some(k) => k.iterations(),
+            None => pwsfile::DEFAULT_ITERATIONS,
+        }
+    }
+
+    /// Re-key with a fresh salt and fresh keys, then rewrite the file
+    pub fn change_master_password(
+        &mut self,
+        current: &str,
+        new: &str,
+        iterations: u32,
+    ) -> Result<(), TuiPwSafeErrors> {
+        if !self.keys.as_ref().is_some_and(|k| k.matches(current)) {
+            return Err(TuiPwSafeErrors::WrongPassphrase);
         }
+        let old = self.keys.replace(SafeKeys::generate(new, iterations));
+        let header = self.db.header.clone();
+        self.db
+            .set_header(HeaderField::LastMasterPasswordChange(now()));
+        if let Err(e) = self.save() {
+            self.keys = old;
+            self.db.header = header;
+            return Err(e);
+        }
+        Ok(())
     }
 
+    /// Records directly in the group, an empty name gives the records without a group
     pub fn by_group_name(&self, name: &str) -> Vec<&DbRecord> {
-        self.pf.by_broup(name.to_string())
+        self.db
+            .records
+            .iter()
+            .filter(|r| r.group().unwrap_or_default() == name)
+            .collect()
+    }
+
+    pub fn records(&self) -> impl Iterator<Item = &DbRecord> {
+        self.db.records.iter()
+    }
+
+    /// Records of the group and of all its subgroups
+    pub fn by_group_path(&self, path: &str) -> Vec<&DbRecord> {
+        let prefix = format!("{}.", path);
+        self.db
+            .records
+            .iter()
+            .filter(|r| {
+                r.group()
+                    .is_some_and(|g| g == path || g.starts_with(&prefix))
+            })
+            .collect()
     }
 
+    /// Groups of all records and the empty groups kept in the header
     pub fn groups(&self) -> HashSet<String> {
-        self.pf.groups()
+        let mut groups: HashSet<String> = self
+            .db
+            .records
+            .iter()
+            .filter_map(|r| r.group())
+            .map(str::to_string)
+            .collect();
+        groups.extend(self.db.header.iter().filter_map(|h| match h {
+            HeaderField::EmptyGroups(g) => Some(g.clone()),
+            _ => None,
+        }));
+        groups
+    }
+
+    /// Password policies the safe keeps by name in its header
+    pub fn named_policies(&self) -> Vec<(String, PasswordPolicy)> {
+        self.db
+            .header
+            .iter()
+            .find_map(|h| match h {
+                HeaderField::NamedPasswordPolicies(raw) => Some(PasswordPolicy::parse_named(raw)),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn named_policy(&self, name: &str) -> Result<PasswordPolicy, TuiPwSafeErrors> {
+        self.named_policies()
+            .into_iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, policy)| policy)
+            .ok_or_else(|| TuiPwSafeErrors::PolicyNotFound(name.to_string()))
+    }
+
+    /// Fuzzy search over all records, best match first
+    pub fn search(&self, query: &str) -> Vec<SearchHit> {
+        search::search(self.db.records.iter(), query)
+    }
+
+    pub fn by_uuid(&self, uuid: Uuid) -> Option<&DbRecord> {
+        self.db.records.iter().find(|r| r.uuid() == Some(uuid))
+    }
+
+    /// Records with a password that expired or expires within the given days, the earliest first
+    pub fn expiring(&self, days: u64) -> Vec<(&DbRecord, u32)> {
+        let until = u64::from(now()).saturating_add(days.saturating_mul(86_400));
+        let mut records: Vec<(&DbRecord, u32)> = self
+            .db
+            .records
+            .iter()
+            .filter_map(|r| r.expiry().map(|expiry| (r, expiry)))
+            .filter(|(_, expiry)| u64::from(*expiry) <= until)
+            .collect();
+        records.sort_by_key(|(r, expiry)| (*expiry, r.title()));
+        records
+    }
+
+    /// One-time codes of the record, if it has a two-factor key
+    pub fn totp(&self, uuid: Uuid) -> Option<Totp> {
+        self.by_uuid(uuid).and_then(Totp::from_record)
+    }
+
+    /// Add a new record, uuid and creation time are set when missing
+    pub fn add_record(&mut self, mut record: DbRecord) -> Result<Uuid, TuiPwSafeErrors> {
+        check_title(&record)?;
+        let uuid = match record.uuid() {
+            Some(u) => u,
+            None => {
+                let u = Uuid::new_v4();
+                record.set(RecordField::Uuid(u));
+                u
+            }
+        };
+        if !record
+            .fields
+            .iter()
+            .any(|f| matches!(f, RecordField::CreationTime(_)))
+        {
+            let now = now();
+            record.set(RecordField::CreationTime(now));
+            record.set(RecordField::PasswordModTime(now));
+            record.set(RecordField::LastModTime(now));
+        }
+        let filled_group = self.filled_group(&record);
+        self.history.push(
+            Edit::Add {
+                uuid: Some(uuid),
+                record,
+                filled_group,
+            },
+            &mut self.db,
+        );
+        Ok(uuid)
+    }
+
+    /// Add a group without entries, stored as empty group in the header
+    pub fn add_group(&mut self, name: &str) {
+        if self.groups().contains(name) {
+            return;
+        }
+        self.history
+            .push(Edit::AddGroup(name.to_string()), &mut self.db);
+    }
+
+    /// Replace the record with the same uuid and update its modification times
+    pub fn update_record(&mut self, mut record: DbRecord) -> Result<(), TuiPwSafeErrors> {
+        check_title(&record)?;
+        let Some(before) = record.uuid().and_then(|u| self.by_uuid(u)) else {
+            return Err(TuiPwSafeErrors::EntryNotFound(String::from(
+                "the changed entry",
+            )));
+        };
+        let now = now();
+        if before.password() != record.password() {
+            record.set(RecordField::PasswordModTime(now));
+        }
+        record.set(RecordField::LastModTime(now));
+        let filled_group = self.filled_group(&record);
+        self.history.push(
+            Edit::Update {
+                record,
+                filled_group,
+            },
+            &mut self.db,
+        );
+        Ok(())
+    }
+
+    pub fn remove_record(&mut self, uuid: Uuid) {
+        if let Some(index) = self.db.records.iter().position(|r| r.uuid() == Some(uuid)) {
+            let edit = Edit::Remove {
+                index,
+                uuid: Some(uuid),
+                record: DbRecord::default(),
+            };
+            self.history.push(edit, &mut self.db);
+        }
+    }
+
+    pub fn move_record(&mut self, uuid: Uuid, group: &str) -> Result<(), TuiPwSafeErrors> {
+        let mut record = self
+            .by_uuid(uuid)
+            .map(Secret::copy_of)
+            .ok_or_else(|| TuiPwSafeErrors::EntryNotFound(uuid.to_string()))?
+            .take();
+        record.set(RecordField::Group(group.to_string()));
+        self.update_record(record)
+    }
+
+    /// Revert the last edit, returns the affected record
+    pub fn undo(&mut self) -> Option<Option<Uuid>> {
+        self.history.undo(&mut self.db).map(Edit::uuid)
+    }
+
+    /// Apply the last undone edit again, returns the affected record
+    pub fn redo(&mut self) -> Option<Option<Uuid>> {
+        self.history.redo(&mut self.db).map(Edit::uuid)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.db.records.is_empty()
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.restored_changes || !self.history.is_empty()
+    }
+
+    // an empty group in the header which the record is placed into
+    fn filled_group(&self, record: &DbRecord) -> Option<String> {
+        let group = record.group()?;
+        self.db
+            .header
+            .iter()
+            .any(|h| matches!(h, HeaderField::EmptyGroups(g) if g == group))
+            .then(|| group.to_string())
+    }
+
+    /// Encrypt the database and replace the file through a temp file and rename
+    pub fn save(&mut self) -> Result<(), TuiPwSafeErrors> {
+        let keys = match &self.keys {
+            Some(k) => k,
+            None => return Err(TuiPwSafeErrors::StoreFileNotRead),
+        };
+        stamp_header(&mut self.db);
+        let data = pwsfile::encrypt(&self.db, keys);
+        write_atomic(&self.path, &data)?;
+        self.data = data;
+        self.history.clear();
+        self.restored_changes = false;
+        Ok(())
+    }
+}
+
+pub fn now() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as u32)
+        .unwrap_or_default()
+}
+
+/// Only the date of [format_time], `YYYY-MM-DD`
+pub fn format_date(time: u32) -> String {
+    format_time(time)[..10].to_string()
+}
+
+/// Format a timestamp of the safe as UTC date and time
+pub fn format_time(time: u32) -> String {
+    let days = i64::from(time) / 86_400;
+    let secs = i64::from(time) % 86_400;
+    // days since epoch to civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60
+    )
+}
+
+/// The format requires a title for every record
+fn check_title(record: &DbRecord) -> Result<(), TuiPwSafeErrors> {
+    match record.title() {
+        Some(title) if !title.trim().is_empty() => Ok(()),
+        _ => Err(TuiPwSafeErrors::UntitledRecord),
+    }
+}
+
+fn stamp_header(db: &mut PwDb) {
+    if !db
+        .header
+        .iter()
+        .any(|h| matches!(h, HeaderField::Version(_)))
+    {
+        // the version has to be the first header field
+        db.header.insert(0, HeaderField::Version(FORMAT_VERSION));
+    }
+    if !db.header.iter().any(|h| matches!(h, HeaderField::Uuid(_))) {
+        db.set_header(HeaderField::Uuid(Uuid::new_v4()));
+    }
+    db.set_header(HeaderField::TimestampLastSaved(now()));
+    db.set_header(HeaderField::WhatLastSaved(APP_NAME.to_string()));
+    if let Ok(user) = env::var("USER").or_else(|_| env::var("USERNAME")) {
+        db.set_header(HeaderField::LastSavedByUser(user));
+    }
+    let host = env::var("HOSTNAME").or_else(|_| fs::read_to_string("/etc/hostname"));
+    if let Ok(host) = host {
+        db.set_header(HeaderField::LastSavedOnHost(host.trim().to_string()));
+    }
+}
+
+/// Write next to the target and rename over it, so a crash leaves either the old or the new file
+fn write_atomic(path: &Path, data: &[u8]) -> io::Result<()> {
+    let name = match path.file_name() {
+        Some(n) => n.to_string_lossy(),
+        None => return Err(io::ErrorKind::InvalidInput.into()),
+    };
+    let tmp = path.with_file_name(format!(".{}.{}.tmp", name, process::id()));
+    let written = File::create(&tmp).and_then(|mut file| {
+        match fs::metadata(path) {
+            Ok(meta) => file.set_permissions(meta.permissions())?,
+            // a new safe is only readable by its owner
+            #[cfg(unix)]
+            Err(_) => file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?,
+            #[cfg(not(unix))]
+            Err(_) => {}
+        }
+        file.write_all(data)?;
+        file.sync_all()
+    });
+    if let Err(e) = written.and_then(|_| fs::rename(&tmp, path)) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e);
+    }
+    // persist the rename itself
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        if let Ok(d) = File::open(dir) {
+            let _ = d.sync_all();
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model() -> SafeModel {
+        let path = env::temp_dir().join(format!("tui-pwsafe-{}-never-saved", process::id()));
+        SafeModel::create(&path.to_string_lossy())
+            .ok()
+            .expect("a path that does not exist")
+    }
+
+    fn add(model: &mut SafeModel, title: &str, group: &str) -> Uuid {
+        let mut record = DbRecord::default();
+        record.set(RecordField::Title(title.to_string()));
+        record.set(RecordField::Group(group.to_string()));
+        model.add_record(record).ok().expect("a titled record")
+    }
+
+    fn group_of(model: &SafeModel, uuid: Uuid) -> Option<&str> {
+        model.by_uuid(uuid).and_then(DbRecord::group)
+    }
+
+    fn empty_groups(model: &SafeModel) -> Vec<&str> {
+        model
+            .db
+            .header
+            .iter()
+            .filter_map(|h| match h {
+                HeaderField::EmptyGroups(g) => Some(g.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn move_record_into_an_empty_group() {
+        let mut model = model();
+        let uuid = add(&mut model, "mail", "Work");
+        model.add_group("Private");
+        assert_eq!(empty_groups(&model), ["Private"]);
+        model
+            .move_record(uuid, "Private")
+            .ok()
+            .expect("an existing record");
+        assert_eq!(group_of(&model, uuid), Some("Private"));
+        // the group is no longer empty
+        assert!(empty_groups(&model).is_empty());
+        assert_eq!(model.undo(), Some(Some(uuid)));
+        assert_eq!(group_of(&model, uuid), Some("Work"));
+        assert_eq!(empty_groups(&model), ["Private"]);
+        assert_eq!(model.redo(), Some(Some(uuid)));
+        assert_eq!(group_of(&model, uuid), Some("Private"));
+        assert!(empty_groups(&model).is_empty());
+        assert!(model.is_dirty());
+    }
+
+    #[test]
+    fn move_keeps_the_other_fields() {
+        let mut model = model();
+        let uuid = add(&mut model, "mail", "Work");
+        let created = model.by_uuid(uuid).and_then(DbRecord::creation_time);
+        model.move_record(uuid, "Old.Work").ok().expect("a record");
+        let record = model.by_uuid(uuid).expect("the moved record");
+        assert_eq!(record.title(), Some("mail"));
+        assert_eq!(record.creation_time(), created);
+        assert_eq!(model.records().count(), 1);
+    }
+
+    #[test]
+    fn undo_of_add_and_remove() {
+        let mut model = model();
+        let first = add(&mut model, "a", "G");
+        let second = add(&mut model, "b", "G");
+        model.remove_record(first);
+        assert!(model.by_uuid(first).is_none());
+        assert_eq!(model.undo(), Some(Some(first)));
+        assert_eq!(
+            model
+                .records()
+                .filter_map(DbRecord::uuid)
+                .collect::<Vec<_>>(),
+            [first, second]
+        );
+        assert_eq!(model.undo(), Some(Some(second)));
+        assert_eq!(model.undo(), Some(Some(first)));
+        assert!(model.is_empty());
+        assert!(!model.is_dirty());
+        assert_eq!(model.undo(), None);
+    }
+
+    #[test]
+    fn move_of_a_missing_record() {
+        let mut model = model();
+        assert!(matches!(
+            model.move_record(Uuid::new_v4(), "Work"),
+            Err(TuiPwSafeErrors::EntryNotFound(_))
+        ));
+        assert!(!model.is_dirty());
     }
 }
@@ -0,0 +1,485 @@
+//! Password policies as stored per entry and in the header of a v3 safe, and passwords made by them
+
+use crate::contracts::TuiPwSafeErrors;
+use crate::contracts::TuiPwSafeErrors::InvalidPolicy;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::str::Chars;
+
+pub const USE_LOWERCASE: u16 = 0x8000;
+pub const USE_UPPERCASE: u16 = 0x4000;
+pub const USE_DIGITS: u16 = 0x2000;
+pub const USE_SYMBOLS: u16 = 0x1000;
+pub const USE_HEX_DIGITS: u16 = 0x0800;
+pub const USE_EASY_VISION: u16 = 0x0400;
+pub const MAKE_PRONOUNCEABLE: u16 = 0x0200;
+
+// character pools of Password Safe, easy vision leaves out what looks alike, e.g. l, 1, O and 0
+const LOWERCASE: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPERCASE: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &str = "0123456789";
+const SYMBOLS: &str = "+-=_@#$%^&;:,.<>/~\\[](){}?!|*";
+const EASY_LOWERCASE: &str = "abcdefghijkmnopqrstuvwxyz";
+const EASY_UPPERCASE: &str = "ABCDEFGHJKLMNPQRTUVWXY";
+const EASY_DIGITS: &str = "346789";
+const EASY_SYMBOLS: &str = "+-=_@#$%^&<>/~\\?*";
+const PRONOUNCEABLE_SYMBOLS: &str = "@&(#!|$+";
+const HEX_DIGITS: &str = "0123456789abcdef";
+const VOWELS: &str = "aeiou";
+
+/// Longest password a policy can describe, the length has three hex digits
+pub const MAX_LENGTH: usize = 0xfff;
+
+/// Flags, length and minimum counts, `ffffnnnllluuudddsss` in hex, and the symbols to use
+#[derive(Debug, Clone, PartialEq)]
+pub struct PasswordPolicy {
+    pub flags: u16,
+    pub length: usize,
+    pub min_lowercase: usize,
+    pub min_uppercase: usize,
+    pub min_digits: usize,
+    pub min_symbols: usize,
+    // the default set of Password Safe when not given
+    pub symbols: Option<String>,
+}
+
+/// The default policy of Password Safe, 12 characters of all classes
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        PasswordPolicy {
+            flags: USE_LOWERCASE | USE_UPPERCASE | USE_DIGITS | USE_SYMBOLS,
+            length: 12,
+            min_lowercase: 1,
+            min_uppercase: 1,
+            min_digits: 1,
+            min_symbols: 1,
+            symbols: None,
+        }
+    }
+}
+
+impl PasswordPolicy {
+    pub fn parse(raw: &str) -> Option<Self> {
+        let hex = |range: std::ops::Range<usize>| {
+            raw.get(range)
+                .and_then(|s| usize::from_str_radix(s, 16).ok())
+        };
+        Some(PasswordPolicy {
+            flags: hex(0..4)? as u16,
+            length: hex(4..7)?,
+            min_lowercase: hex(7..10)?,
+            min_uppercase: hex(10..13)?,
+            min_digits: hex(13..16)?,
+            min_symbols: hex(16..19)?,
+            symbols: None,
+        })
+    }
+
+    /// Named policies of the header field, each is the length of the name, the name, the policy
+    /// and the length of the symbols followed by the symbols, lengths are two hex digits
+    pub fn parse_named(raw: &str) -> Vec<(String, PasswordPolicy)> {
+        let mut chars = raw.chars();
+        let count = hex_part(&mut chars, 2).unwrap_or(0);
+        // the rest can't be found once one policy is cut short
+        (0..count)
+            .map_while(|_| {
+                let name_len = hex_part(&mut chars, 2)?;
+                let name = part(&mut chars, name_len)?;
+                let mut policy = PasswordPolicy::parse(&part(&mut chars, 19)?)?;
+                let symbols_len = hex_part(&mut chars, 2)?;
+                policy.symbols = Some(part(&mut chars, symbols_len)?).filter(|s| !s.is_empty());
+                Some((name, policy))
+            })
+            .collect()
+    }
+
+    pub fn has(&self, flag: u16) -> bool {
+        self.flags & flag != 0
+    }
+
+    pub fn toggle(&mut self, flag: u16) {
+        self.flags ^= flag;
+    }
+
+    /// Short human readable summary, e.g. `16 chars: 2+ lower, 1+ upper, digits`
+    pub fn describe(&self) -> String {
+        if self.has(USE_HEX_DIGITS) {
+            return format!("{} hex digits", self.length);
+        }
+        let classes = [
+            (USE_LOWERCASE, "lower", self.min_lowercase),
+            (USE_UPPERCASE, "upper", self.min_uppercase),
+            (USE_DIGITS, "digits", self.min_digits),
+            (USE_SYMBOLS, "symbols", self.min_symbols),
+        ];
+        let mut parts: Vec<String> = classes
+            .iter()
+            .filter(|(flag, _, _)| self.has(*flag))
+            .map(|(_, name, min)| match min {
+                0 => name.to_string(),
+                n => format!("{}+ {}", n, name),
+            })
+            .collect();
+        if self.has(USE_EASY_VISION) {
+            parts.push("easy vision".to_string());
+        }
+        if self.has(MAKE_PRONOUNCEABLE) {
+            parts.push("pronounceable".to_string());
+        }
+        format!("{} chars: {}", self.length, parts.join(", "))
+    }
+
+    /// Whether passwords can be made by the policy
+    pub fn check(&self) -> Result<(), TuiPwSafeErrors> {
+        if self.length == 0 || self.length > MAX_LENGTH {
+            return Err(InvalidPolicy(format!(
+                "the length has to be between 1 and {}",
+                MAX_LENGTH
+            )));
+        }
+        if self.has(USE_HEX_DIGITS) {
+            return Ok(());
+        }
+        if self.classes().is_empty() {
+            return Err(InvalidPolicy("no characters to choose from".to_string()));
+        }
+        if self.has(MAKE_PRONOUNCEABLE) {
+            if self.has(USE_EASY_VISION) {
+                return Err(InvalidPolicy(
+                    "easy vision and pronounceable can't be combined".to_string(),
+                ));
+            }
+            if !self.has(USE_LOWERCASE) && !self.has(USE_UPPERCASE) {
+                return Err(InvalidPolicy(
+                    "pronounceable passwords need letters".to_string(),
+                ));
+            }
+        }
+        let required: usize = self.classes().iter().map(|(_, min)| min).sum();
+        if required > self.length {
+            return Err(InvalidPolicy(format!(
+                "the minimum counts add up to {}, more than the length of {}",
+                required, self.length
+            )));
+        }
+        Ok(())
+    }
+
+    /// A new random password, the characters of each class are spread over the whole password
+    pub fn generate(&self) -> Result<String, TuiPwSafeErrors> {
+        self.check()?;
+        let mut rng = rand::thread_rng();
+        let pick =
+            |pool: &[char], rng: &mut rand::rngs::ThreadRng| pool[rng.gen_range(0..pool.len())];
+        if self.has(USE_HEX_DIGITS) {
+            let pool: Vec<char> = HEX_DIGITS.chars().collect();
+            return Ok((0..self.length).map(|_| pick(&pool, &mut rng)).collect());
+        }
+        if self.has(MAKE_PRONOUNCEABLE) {
+            return Ok(self.pronounceable(&mut rng));
+        }
+        let classes = self.classes();
+        let mut password: Vec<char> = Vec::with_capacity(self.length);
+        for (pool, min) in &classes {
+            password.extend((0..*min).map(|_| pick(pool, &mut rng)));
+        }
+        let all: Vec<char> = classes.iter().flat_map(|(pool, _)| pool.clone()).collect();
+        while password.len() < self.length {
+            password.push(pick(&all, &mut rng));
+        }
+        password.shuffle(&mut rng);
+        Ok(password.into_iter().collect())
+    }
+
+    /// Bits of entropy of a password made by the policy, an upper bound that ignores the
+    /// minimum counts
+    pub fn entropy(&self) -> f64 {
+        if self.check().is_err() {
+            return 0.0;
+        }
+        if self.has(USE_HEX_DIGITS) {
+            return self.length as f64 * 4.0;
+        }
+        if self.has(MAKE_PRONOUNCEABLE) {
+            let consonants = self.consonants().len() as f64;
+            let vowels = VOWELS.len() as f64;
+            let letters = self.length - self.min(USE_DIGITS) - self.min(USE_SYMBOLS);
+            // alternating consonants and vowels, the case of a letter counts once per letter
+            let per_pair = consonants.log2() + vowels.log2();
+            let case = if self.has(USE_UPPERCASE) && self.has(USE_LOWERCASE) {
+                1.0
+            } else {
+                0.0
+            };
+            let inserted = [USE_DIGITS, USE_SYMBOLS]
+                .iter()
+                .map(|&class| self.min(class) as f64 * (self.pool(class).len() as f64).log2())
+                .sum::<f64>();
+            return letters as f64 * (per_pair / 2.0 + case) + inserted;
+        }
+        let pool: usize = self.classes().iter().map(|(pool, _)| pool.len()).sum();
+        self.length as f64 * (pool as f64).log2()
+    }
+
+    /// Minimum count of a class, also while the class is not used
+    pub fn min_count(&self, class: u16) -> usize {
+        match class {
+            USE_LOWERCASE => self.min_lowercase,
+            USE_UPPERCASE => self.min_uppercase,
+            USE_DIGITS => self.min_digits,
+            _ => self.min_symbols,
+        }
+    }
+
+    pub fn set_min_count(&mut self, class: u16, count: usize) {
+        match class {
+            USE_LOWERCASE => self.min_lowercase = count,
+            USE_UPPERCASE => self.min_uppercase = count,
+            USE_DIGITS => self.min_digits = count,
+            _ => self.min_symbols = count,
+        }
+    }
+
+    /// Minimum count of a class, none if the class is not used
+    fn min(&self, class: u16) -> usize {
+        if self.has(class) {
+            self.min_count(class)
+        } else {
+            0
+        }
+    }
+
+    /// The given symbols or the default set that applies to the policy
+    pub fn symbol_set(&self) -> String {
+        self.pool(USE_SYMBOLS).into_iter().collect()
+    }
+
+    /// Pool and minimum count of every class in use
+    fn classes(&self) -> Vec<(Vec<char>, usize)> {
+        [USE_LOWERCASE, USE_UPPERCASE, USE_DIGITS, USE_SYMBOLS]
+            .into_iter()
+            .filter(|&class| self.has(class))
+            .map(|class| (self.pool(class), self.min(class)))
+            .filter(|(pool, _)| !pool.is_empty())
+            .collect()
+    }
+
+    fn pool(&self, class: u16) -> Vec<char> {
+        let easy = self.has(USE_EASY_VISION);
+        let chars = match class {
+            USE_LOWERCASE if easy => EASY_LOWERCASE,
+            USE_LOWERCASE => LOWERCASE,
+            USE_UPPERCASE if easy => EASY_UPPERCASE,
+            USE_UPPERCASE => UPPERCASE,
+            USE_DIGITS if easy => EASY_DIGITS,
+            USE_DIGITS => DIGITS,
+            _ => match &self.symbols {
+                Some(symbols) => symbols.as_str(),
+                None if self.has(MAKE_PRONOUNCEABLE) => PRONOUNCEABLE_SYMBOLS,
+                None if easy => EASY_SYMBOLS,
+                None => SYMBOLS,
+            },
+        };
+        chars.chars().collect()
+    }
+
+    fn consonants(&self) -> Vec<char> {
+        self.pool(USE_LOWERCASE)
+            .into_iter()
+            .filter(|c| !VOWELS.contains(*c))
+            .collect()
+    }
+
+    /// Alternating consonants and vowels with the minimum of digits and symbols put in between,
+    /// and the minimum of uppercase letters, or only uppercase letters without lowercase ones
+    fn pronounceable(&self, rng: &mut rand::rngs::ThreadRng) -> String {
+        let consonants = self.consonants();
+        let vowels: Vec<char> = VOWELS.chars().collect();
+        let letters = self.length - self.min(USE_DIGITS) - self.min(USE_SYMBOLS);
+        let mut vowel = rng.gen_bool(0.5);
+        let mut password: Vec<char> = (0..letters)
+            .map(|_| {
+                vowel = !vowel;
+                let pool = if vowel { &vowels } else { &consonants };
+                pool[rng.gen_range(0..pool.len())]
+            })
+            .collect();
+        if !self.has(USE_LOWERCASE) {
+            password.iter_mut().for_each(|c| c.make_ascii_uppercase());
+        } else if self.has(USE_UPPERCASE) {
+            let mut positions: Vec<usize> = (0..letters).collect();
+            positions.shuffle(rng);
+            // at least the minimum, and about a quarter of the letters
+            let upper = self.min(USE_UPPERCASE).max(letters / 4);
+            for &i in positions.iter().take(upper) {
+                password[i].make_ascii_uppercase();
+            }
+        }
+        for class in [USE_DIGITS, USE_SYMBOLS] {
+            let pool = self.pool(class);
+            for _ in 0..self.min(class) {
+                let at = rng.gen_range(0..=password.len());
+                password.insert(at, pool[rng.gen_range(0..pool.len())]);
+            }
+        }
+        password.into_iter().collect()
+    }
+}
+
+fn part(chars: &mut Chars, n: usize) -> Option<String> {
+    let part: String = chars.take(n).collect();
+    (part.chars().count() == n).then_some(part)
+}
+
+fn hex_part(chars: &mut Chars, n: usize) -> Option<usize> {
+    usize::from_str_radix(&part(chars, n)?, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(flags: u16, length: usize) -> PasswordPolicy {
+        PasswordPolicy {
+            flags,
+            length,
+            ..PasswordPolicy::default()
+        }
+    }
+
+    fn generate(policy: &PasswordPolicy) -> Vec<String> {
+        (0..50)
+            .map(|_| policy.generate().ok().expect("a valid policy"))
+            .collect()
+    }
+
+    fn count(password: &str, pool: &str) -> usize {
+        password.chars().filter(|c| pool.contains(*c)).count()
+    }
+
+    #[test]
+    fn parse_hex_fields() {
+        assert_eq!(
+            PasswordPolicy::parse("f00000c001001001001"),
+            Some(PasswordPolicy::default())
+        );
+        let policy = PasswordPolicy::parse("a80001400a000002000").unwrap();
+        assert_eq!(policy.flags, USE_LOWERCASE | USE_DIGITS | USE_HEX_DIGITS);
+        assert_eq!(policy.length, 20);
+        assert_eq!(policy.min_lowercase, 10);
+        assert_eq!(policy.min_uppercase, 0);
+        assert_eq!(policy.min_digits, 2);
+        assert_eq!(policy.min_symbols, 0);
+        assert_eq!(PasswordPolicy::parse("f00000c00100100100"), None);
+        assert_eq!(PasswordPolicy::parse("f00000c0010010010zz"), None);
+    }
+
+    #[test]
+    fn parse_named_policies() {
+        let raw = "0203webf00000c00100100100102!?04pinsa00000400000000400000";
+        let named = PasswordPolicy::parse_named(raw);
+        assert_eq!(named.len(), 2);
+        assert_eq!(named[0].0, "web");
+        assert_eq!(named[0].1.symbols.as_deref(), Some("!?"));
+        assert_eq!(named[1].0, "pins");
+        assert_eq!(named[1].1.flags, USE_LOWERCASE | USE_DIGITS);
+        assert_eq!(named[1].1.length, 4);
+        assert_eq!(named[1].1.symbols, None);
+        // a policy cut short ends the list
+        assert_eq!(PasswordPolicy::parse_named(&raw[..40]).len(), 1);
+        assert!(PasswordPolicy::parse_named("").is_empty());
+    }
+
+    #[test]
+    fn minimum_counts() {
+        let mut policy = policy(USE_LOWERCASE | USE_DIGITS | USE_SYMBOLS, 8);
+        policy.min_digits = 5;
+        policy.min_symbols = 2;
+        for password in generate(&policy) {
+            assert_eq!(password.chars().count(), 8);
+            assert!(count(&password, DIGITS) >= 5, "{}", password);
+            assert!(count(&password, SYMBOLS) >= 2, "{}", password);
+            assert!(count(&password, LOWERCASE) >= 1, "{}", password);
+        }
+        policy.min_lowercase = 2;
+        assert!(matches!(policy.check(), Err(InvalidPolicy(_))));
+        // the counts of classes that are not used don't matter
+        policy.flags = USE_DIGITS;
+        assert!(policy.check().is_ok());
+    }
+
+    #[test]
+    fn invalid_policies() {
+        for policy in [
+            policy(USE_LOWERCASE, 0),
+            policy(USE_LOWERCASE, MAX_LENGTH + 1),
+            policy(0, 12),
+            policy(USE_DIGITS | MAKE_PRONOUNCEABLE, 12),
+            policy(USE_LOWERCASE | USE_EASY_VISION | MAKE_PRONOUNCEABLE, 12),
+        ] {
+            assert!(policy.generate().is_err(), "{}", policy.describe());
+            assert_eq!(policy.entropy(), 0.0, "{}", policy.describe());
+        }
+    }
+
+    #[test]
+    fn hex_digits_only() {
+        // the other classes are ignored
+        let policy = policy(USE_HEX_DIGITS | USE_UPPERCASE | USE_SYMBOLS, 32);
+        for password in generate(&policy) {
+            assert_eq!(password.len(), 32);
+            assert_eq!(count(&password, HEX_DIGITS), 32, "{}", password);
+        }
+        assert_eq!(policy.describe(), "32 hex digits");
+    }
+
+    #[test]
+    fn easy_vision_leaves_out_lookalikes() {
+        let policy = policy(
+            USE_LOWERCASE | USE_UPPERCASE | USE_DIGITS | USE_SYMBOLS | USE_EASY_VISION,
+            64,
+        );
+        let easy = [EASY_LOWERCASE, EASY_UPPERCASE, EASY_DIGITS, EASY_SYMBOLS].concat();
+        for password in generate(&policy) {
+            assert_eq!(count(&password, &easy), 64, "{}", password);
+            assert_eq!(count(&password, "lIO01"), 0, "{}", password);
+        }
+    }
+
+    #[test]
+    fn pronounceable_alternates_consonants_and_vowels() {
+        let mut pronounceable = policy(USE_LOWERCASE | MAKE_PRONOUNCEABLE, 16);
+        for password in generate(&pronounceable) {
+            let vowels: Vec<bool> = password.chars().map(|c| VOWELS.contains(c)).collect();
+            assert_eq!(vowels.len(), 16);
+            assert!(vowels.windows(2).all(|w| w[0] != w[1]), "{}", password);
+        }
+        pronounceable.flags |= USE_UPPERCASE | USE_DIGITS;
+        pronounceable.min_uppercase = 3;
+        pronounceable.min_digits = 2;
+        for password in generate(&pronounceable) {
+            assert_eq!(password.chars().count(), 16);
+            assert!(count(&password, UPPERCASE) >= 3, "{}", password);
+            assert_eq!(count(&password, DIGITS), 2, "{}", password);
+        }
+        // without lowercase all letters are capitals
+        pronounceable.flags = USE_UPPERCASE | MAKE_PRONOUNCEABLE;
+        for password in generate(&pronounceable) {
+            assert_eq!(count(&password, UPPERCASE), 16, "{}", password);
+        }
+    }
+
+    #[test]
+    fn entropy() {
+        let all = PasswordPolicy::default();
+        assert!((all.entropy() - 12.0 * 91f64.log2()).abs() < 1e-9);
+        assert_eq!(policy(USE_HEX_DIGITS, 16).entropy(), 64.0);
+        let mut digits = policy(USE_DIGITS, 6);
+        assert!((digits.entropy() - 6.0 * 10f64.log2()).abs() < 1e-9);
+        digits.flags |= USE_EASY_VISION;
+        assert!((digits.entropy() - 6.0 * 6f64.log2()).abs() < 1e-9);
+        let pronounceable = policy(USE_LOWERCASE | MAKE_PRONOUNCEABLE, 8);
+        let expected = 8.0 * (21f64.log2() + 5f64.log2()) / 2.0;
+        assert!((pronounceable.entropy() - expected).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,467 @@
+//! Encryption and decryption of the Password Safe v3 container
+//!
+//! TAG | SALT | ITER | H(P') | B1 B2 | B3 B4 | IV | HDR + RECORDS (twofish-cbc) | EOF | HMAC
+use crate::contracts::TuiPwSafeErrors;
+use crate::contracts::TuiPwSafeErrors::{CorruptFile, WrongPassphrase};
+use crate::model::header::HeaderField;
+use crate::model::record::{DbRecord, RecordField};
+use hmac::{Hmac, Mac};
+use lsx::twofish::Twofish;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use zeroize::{Zeroize, Zeroizing};
+
+const TAG: &[u8] = b"PWS3";
+const EOF: &[u8] = b"PWS3-EOFPWS3-EOF";
+const BLOCK_SIZE: usize = 16;
+const SALT_SIZE: usize = 32;
+const KEY_SIZE: usize = 32;
+const HMAC_SIZE: usize = 32;
+// tag, salt, iter, H(P'), B1-B4 and IV
+const PREAMBLE_SIZE: usize = 4 + SALT_SIZE + 4 + KEY_SIZE + 4 * BLOCK_SIZE + BLOCK_SIZE;
+const END_OF_ENTRY: u8 = 0xff;
+
+/// Lowest key stretch iteration count the format allows
+pub const MIN_ITERATIONS: u32 = 2048;
+/// Highest iteration count pwsafe accepts, a larger count in a file would stall the unlock
+pub const MAX_ITERATIONS: u32 = 1 << 22;
+/// Iteration count used for new safes and suggested when re-keying
+pub const DEFAULT_ITERATIONS: u32 = 262_144;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Key material of an unlocked safe, kept to save without asking for the passphrase again
+pub struct SafeKeys {
+    salt: [u8; SALT_SIZE],
+    iter: u32,
+    stretched: [u8; KEY_SIZE],
+    k: [u8; KEY_SIZE],
+    l: [u8; KEY_SIZE],
+}
+
+/// Decrypted header and records of a safe
+#[derive(Default)]
+pub struct PwDb {
+    pub header: Vec<HeaderField>,
+    pub records: Vec<DbRecord>,
+}
+
+impl PwDb {
+    /// Replaces the header field of the same kind or appends it
+    pub fn set_header(&mut self, field: HeaderField) {
+        match self.header.iter_mut().find(|h| h.kind() == field.kind()) {
+            Some(existing) => *existing = field,
+            None => self.header.push(field),
+        }
+    }
+}
+
+impl SafeKeys {
+    /// Fresh salt, record key and hmac key for the given passphrase
+    pub fn generate(phrase: &str, iter: u32) -> Self {
+        let mut rng = rand::thread_rng();
+        let mut salt = [0u8; SALT_SIZE];
+        let mut k = [0u8; KEY_SIZE];
+        let mut l = [0u8; KEY_SIZE];
+        rng.fill_bytes(&mut salt);
+        rng.fill_bytes(&mut k);
+        rng.fill_bytes(&mut l);
+        let iter = iter.clamp(MIN_ITERATIONS, MAX_ITERATIONS);
+        SafeKeys {
+            stretched: stretch_key(phrase.as_bytes(), &salt, iter),
+            salt,
+            iter,
+            k,
+            l,
+        }
+    }
+
+    pub fn iterations(&self) -> u32 {
+        self.iter
+    }
+
+    /// Check a passphrase against the one the keys were derived from
+    pub fn matches(&self, phrase: &str) -> bool {
+        stretch_key(phrase.as_bytes(), &self.salt, self.iter) == self.stretched
+    }
+}
+
+impl Drop for SafeKeys {
+    fn drop(&mut self) {
+        self.stretched.zeroize();
+        self.k.zeroize();
+        self.l.zeroize();
+    }
+}
+
+/// Check tag, minimal size and iteration count of an encrypted safe
+pub fn check_format(bytes: &[u8]) -> Result<(), TuiPwSafeErrors> {
+    if bytes.len() < PREAMBLE_SIZE + EOF.len() + HMAC_SIZE || !bytes.starts_with(TAG) {
+        return Err(CorruptFile);
+    }
+    let eof = bytes.len() - HMAC_SIZE - EOF.len();
+    if &bytes[eof..eof + EOF.len()] != EOF || !(eof - PREAMBLE_SIZE).is_multiple_of(BLOCK_SIZE) {
+        return Err(CorruptFile);
+    }
+    if iterations(bytes) > MAX_ITERATIONS {
+        return Err(CorruptFile);
+    }
+    Ok(())
+}
+
+fn iterations(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[36], bytes[37], bytes[38], bytes[39]])
+}
+
+/// Derive the keys from the passphrase, decrypt and verify header and records
+pub fn decrypt(bytes: &[u8], phrase: &str) -> Result<(SafeKeys, PwDb), TuiPwSafeErrors> {
+    check_format(bytes)?;
+    let mut salt = [0u8; SALT_SIZE];
+    salt.copy_from_slice(&bytes[4..36]);
+    let iter = iterations(bytes);
+    // wiped on every early return, copied into the keys at the end
+    let stretched = Zeroizing::new(stretch_key(phrase.as_bytes(), &salt, iter));
+    if Sha256::digest(*stretched).as_slice() != &bytes[40..72] {
+        return Err(WrongPassphrase);
+    }
+
+    let twofish = Twofish::new256(&stretched);
+    let k = Zeroizing::new(decrypt_key(&twofish, &bytes[72..104]));
+    let l = Zeroizing::new(decrypt_key(&twofish, &bytes[104..136]));
+    let eof = bytes.len() - HMAC_SIZE - EOF.len();
+    // the whole database in plain text, wiped once the fields are parsed
+    let plain = Zeroizing::new(cbc_decrypt(
+        &Twofish::new256(&k),
+        &bytes[136..PREAMBLE_SIZE],
+        &bytes[PREAMBLE_SIZE..eof],
+    ));
+
+    let mut mac = HmacSha256::new_from_slice(&*l).map_err(|_| CorruptFile)?;
+    let mut fields = FieldReader {
+        data: &plain,
+        pos: 0,
+    };
+    let mut db = PwDb::default();
+    let mut header_end = false;
+    while let Some((kind, data)) = fields.next()? {
+        mac.update(data);
+        if kind == END_OF_ENTRY {
+            header_end = true;
+            break;
+        }
+        db.header.push(HeaderField::from_raw(kind, data));
+    }
+    if !header_end {
+        return Err(CorruptFile);
+    }
+    let mut record = DbRecord::default();
+    while let Some((kind, data)) = fields.next()? {
+        mac.update(data);
+        if kind == END_OF_ENTRY {
+            db.records.push(std::mem::take(&mut record));
+        } else {
+            record.fields.push(RecordField::from_raw(kind, data));
+        }
+    }
+    // a record has to end with its end-of-entry field
+    if !record.fields.is_empty() {
+        return Err(CorruptFile);
+    }
+    mac.verify_slice(&bytes[bytes.len() - HMAC_SIZE..])
+        .map_err(|_| CorruptFile)?;
+
+    Ok((
+        SafeKeys {
+            salt,
+            iter,
+            stretched: *stretched,
+            k: *k,
+            l: *l,
+        },
+        db,
+    ))
+}
+
+/// Serialize and encrypt header and records with a fresh IV
+pub fn encrypt(db: &PwDb, keys: &SafeKeys) -> Vec<u8> {
+    let mut rng = rand::thread_rng();
+    let mut mac = HmacSha256::new_from_slice(&keys.l).expect("HMAC accepts any key size");
+    // the serialized fields in the order of the file, each one wiped after use
+    let fields = || {
+        let records = db.records.iter().flat_map(|r| {
+            let fields = r
+                .fields
+                .iter()
+                .map(|f| (f.kind(), Zeroizing::new(f.to_bytes())));
+            fields.chain([(END_OF_ENTRY, Zeroizing::new(Vec::new()))])
+        });
+        db.header
+            .iter()
+            .map(|h| (h.kind(), Zeroizing::new(h.to_bytes())))
+            .chain([(END_OF_ENTRY, Zeroizing::new(Vec::new()))])
+            .chain(records)
+    };
+    // sized up front, growing would leave copies of the plain text behind
+    let size = fields().map(|(_, data)| padded_size(data.len())).sum();
+    let mut plain = Zeroizing::new(Vec::with_capacity(size));
+    for (kind, data) in fields() {
+        mac.update(&data);
+        push_field(&mut plain, kind, &data, &mut rng);
+    }
+    seal(keys, &plain, &mac.finalize().into_bytes())
+}
+
+// preamble, encrypted fields, EOF marker and the HMAC of the field data
+fn seal(keys: &SafeKeys, plain: &[u8], hmac: &[u8]) -> Vec<u8> {
+    let mut rng = rand::thread_rng();
+    let mut iv = [0u8; BLOCK_SIZE];
+    rng.fill_bytes(&mut iv);
+    let twofish = Twofish::new256(&keys.stretched);
+
+    let mut out = Vec::with_capacity(PREAMBLE_SIZE + plain.len() + EOF.len() + HMAC_SIZE);
+    out.extend_from_slice(TAG);
+    out.extend_from_slice(&keys.salt);
+    out.extend_from_slice(&keys.iter.to_le_bytes());
+    out.extend_from_slice(Sha256::digest(keys.stretched).as_slice());
+    out.extend_from_slice(&encrypt_key(&twofish, &keys.k));
+    out.extend_from_slice(&encrypt_key(&twofish, &keys.l));
+    out.extend_from_slice(&iv);
+    out.extend_from_slice(&cbc_encrypt(&Twofish::new256(&keys.k), &iv, plain));
+    out.extend_from_slice(EOF);
+    out.extend_from_slice(hmac);
+    out
+}
+
+struct FieldReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> FieldReader<'a> {
+    // every field starts on a block boundary: length (4), type (1), data, random padding
+    fn next(&mut self) -> Result<Option<(u8, &'a [u8])>, TuiPwSafeErrors> {
+        if self.pos + BLOCK_SIZE > self.data.len() {
+            return Ok(None);
+        }
+        let head = &self.data[self.pos..];
+        let len = u32::from_le_bytes([head[0], head[1], head[2], head[3]]) as usize;
+        let kind = head[4];
+        if len > head.len() - 5 {
+            return Err(CorruptFile);
+        }
+        self.pos += padded_size(len);
+        Ok(Some((kind, &head[5..5 + len])))
+    }
+}
+
+fn push_field(out: &mut Vec<u8>, kind: u8, data: &[u8], rng: &mut impl RngCore) {
+    let start = out.len();
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.push(kind);
+    out.extend_from_slice(data);
+    let mut padding = vec![0u8; padded_size(data.len()) - (out.len() - start)];
+    rng.fill_bytes(&mut padding);
+    out.extend_from_slice(&padding);
+}
+
+// length (4), type (1) and data, padded to whole blocks
+fn padded_size(len: usize) -> usize {
+    (5 + len).div_ceil(BLOCK_SIZE) * BLOCK_SIZE
+}
+
+fn stretch_key(phrase: &[u8], salt: &[u8; SALT_SIZE], iter: u32) -> [u8; KEY_SIZE] {
+    let mut hasher = Sha256::new();
+    hasher.update(phrase);
+    hasher.update(salt);
+    let mut key = hasher.finalize();
+    for _ in 0..iter {
+        key = Sha256::digest(key);
+    }
+    key.into()
+}
+
+fn decrypt_key(twofish: &Twofish, blocks: &[u8]) -> [u8; KEY_SIZE] {
+    let mut key = [0u8; KEY_SIZE];
+    for (i, chunk) in blocks.chunks_exact(BLOCK_SIZE).enumerate() {
+        let mut out = [0u8; BLOCK_SIZE];
+        twofish.decrypt(chunk.try_into().unwrap(), &mut out);
+        key[i * BLOCK_SIZE..(i + 1) * BLOCK_SIZE].copy_from_slice(&out);
+    }
+    key
+}
+
+fn encrypt_key(twofish: &Twofish, key: &[u8; KEY_SIZE]) -> [u8; KEY_SIZE] {
+    let mut blocks = [0u8; KEY_SIZE];
+    for (i, chunk) in key.chunks_exact(BLOCK_SIZE).enumerate() {
+        let mut out = [0u8; BLOCK_SIZE];
+        twofish.encrypt(chunk.try_into().unwrap(), &mut out);
+        blocks[i * BLOCK_SIZE..(i + 1) * BLOCK_SIZE].copy_from_slice(&out);
+    }
+    blocks
+}
+
+fn cbc_decrypt(twofish: &Twofish, iv: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(data.len());
+    let mut prev: [u8; BLOCK_SIZE] = iv.try_into().unwrap();
+    for chunk in data.chunks_exact(BLOCK_SIZE) {
+        let block: [u8; BLOCK_SIZE] = chunk.try_into().unwrap();
+        let mut plain = [0u8; BLOCK_SIZE];
+        twofish.decrypt(&block, &mut plain);
+        plain.iter_mut().zip(prev).for_each(|(a, b)| *a ^= b);
+        result.extend_from_slice(&plain);
+        prev = block;
+        plain.zeroize();
+    }
+    result
+}
+
+fn cbc_encrypt(twofish: &Twofish, iv: &[u8; BLOCK_SIZE], data: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(data.len());
+    let mut prev = *iv;
+    for chunk in data.chunks_exact(BLOCK_SIZE) {
+        let mut block: [u8; BLOCK_SIZE] = chunk.try_into().unwrap();
+        block.iter_mut().zip(prev).for_each(|(a, b)| *a ^= b);
+        twofish.encrypt(&block, &mut prev);
+        result.extend_from_slice(&prev);
+        block.zeroize();
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PHRASE: &str = "correct horse battery staple";
+
+    fn safe() -> (SafeKeys, Vec<u8>) {
+        let keys = SafeKeys::generate(PHRASE, MIN_ITERATIONS);
+        let mut db = PwDb::default();
+        db.set_header(HeaderField::Version(0x030d));
+        let mut record = DbRecord::default();
+        record.set(RecordField::Title("mail".to_string()));
+        record.set(RecordField::Password(
+            "s3cret, with a long tail".to_string(),
+        ));
+        record.set(RecordField::CreationTime(1_700_000_000));
+        record.set(RecordField::TwoFactorKey(vec![1, 2, 3]));
+        db.records.push(record);
+        db.records.push(DbRecord::default());
+        let bytes = encrypt(&db, &keys);
+        (keys, bytes)
+    }
+
+    #[test]
+    fn round_trip() {
+        let (_, bytes) = safe();
+        let (keys, db) = decrypt(&bytes, PHRASE).unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!(keys.iterations(), MIN_ITERATIONS);
+        assert!(keys.matches(PHRASE));
+        assert!(matches!(db.header[..], [HeaderField::Version(0x030d)]));
+        assert_eq!(db.records.len(), 2);
+        let record = &db.records[0];
+        assert_eq!(record.title(), Some("mail"));
+        assert_eq!(record.password(), Some("s3cret, with a long tail"));
+        assert_eq!(record.creation_time(), Some(1_700_000_000));
+        assert_eq!(record.two_factor_key(), Some(&[1u8, 2, 3][..]));
+        assert!(db.records[1].fields.is_empty());
+        // a fresh IV every time
+        assert_ne!(safe().1[PREAMBLE_SIZE..], bytes[PREAMBLE_SIZE..]);
+    }
+
+    #[test]
+    fn wrong_passphrase() {
+        let (_, bytes) = safe();
+        assert!(matches!(decrypt(&bytes, "wrong"), Err(WrongPassphrase)));
+    }
+
+    #[test]
+    fn truncated() {
+        let (_, bytes) = safe();
+        for len in [
+            0,
+            4,
+            PREAMBLE_SIZE,
+            bytes.len() - 1,
+            bytes.len() - HMAC_SIZE,
+        ] {
+            assert!(matches!(check_format(&bytes[..len]), Err(CorruptFile)));
+            assert!(matches!(decrypt(&bytes[..len], PHRASE), Err(CorruptFile)));
+        }
+        // whole blocks cut from the records
+        let mut cut = bytes[..PREAMBLE_SIZE + BLOCK_SIZE].to_vec();
+        cut.extend_from_slice(&bytes[bytes.len() - HMAC_SIZE - EOF.len()..]);
+        assert!(matches!(decrypt(&cut, PHRASE), Err(CorruptFile)));
+    }
+
+    #[test]
+    fn tampered() {
+        let (_, bytes) = safe();
+        let mut records = bytes.clone();
+        records[PREAMBLE_SIZE + 2 * BLOCK_SIZE] ^= 1;
+        assert!(matches!(decrypt(&records, PHRASE), Err(CorruptFile)));
+        let mut hmac = bytes.clone();
+        let last = hmac.len() - 1;
+        hmac[last] ^= 1;
+        assert!(matches!(decrypt(&hmac, PHRASE), Err(CorruptFile)));
+    }
+
+    #[test]
+    fn iterations_are_bounded() {
+        let (_, mut bytes) = safe();
+        bytes[36..40].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(check_format(&bytes), Err(CorruptFile)));
+        assert!(matches!(decrypt(&bytes, PHRASE), Err(CorruptFile)));
+        assert_eq!(
+            SafeKeys::generate(PHRASE, u32::MAX).iterations(),
+            MAX_ITERATIONS
+        );
+    }
+
+    // fields as they are, without the checks of encrypt
+    fn sealed(keys: &SafeKeys, fields: &[(u8, &[u8])]) -> Vec<u8> {
+        let mut plain = Vec::new();
+        let mut mac = HmacSha256::new_from_slice(&keys.l).unwrap();
+        for (kind, data) in fields {
+            push_field(&mut plain, *kind, data, &mut rand::thread_rng());
+            mac.update(data);
+        }
+        seal(keys, &plain, &mac.finalize().into_bytes())
+    }
+
+    #[test]
+    fn header_without_end() {
+        let keys = SafeKeys::generate(PHRASE, MIN_ITERATIONS);
+        let version = HeaderField::Version(0x030d).to_bytes();
+        let bytes = sealed(&keys, &[(0x00, &version)]);
+        assert!(matches!(decrypt(&bytes, PHRASE), Err(CorruptFile)));
+    }
+
+    #[test]
+    fn record_without_end() {
+        let keys = SafeKeys::generate(PHRASE, MIN_ITERATIONS);
+        let bytes = sealed(&keys, &[(END_OF_ENTRY, &[]), (0x03, b"mail")]);
+        assert!(matches!(decrypt(&bytes, PHRASE), Err(CorruptFile)));
+        let bytes = sealed(
+            &keys,
+            &[(END_OF_ENTRY, &[]), (0x03, b"mail"), (END_OF_ENTRY, &[])],
+        );
+        assert!(decrypt(&bytes, PHRASE).is_ok());
+    }
+
+    #[test]
+    fn text_that_is_not_utf8_is_kept() {
+        let keys = SafeKeys::generate(PHRASE, MIN_ITERATIONS);
+        let title = b"caf\xe9";
+        let bytes = sealed(
+            &keys,
+            &[(END_OF_ENTRY, &[]), (0x03, title), (END_OF_ENTRY, &[])],
+        );
+        let (keys, db) = decrypt(&bytes, PHRASE).unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!(db.records[0].title(), None);
+        let (_, db) = decrypt(&encrypt(&db, &keys), PHRASE).unwrap_or_else(|e| panic!("{}", e));
+        let field = &db.records[0].fields[0];
+        assert_eq!(field.kind(), 0x03);
+        assert_eq!(field.to_bytes(), title);
+    }
+}
@@ -0,0 +1,385 @@
+use uuid::Uuid;
+use zeroize::{Zeroize, Zeroizing};
+
+/// Typed record fields as defined in section 3.4 of the Password Safe v3 format,
+/// neither `Clone` nor `Debug` so that passwords are only copied on purpose and never printed
+#[derive(PartialEq)]
+pub enum RecordField {
+    Uuid(Uuid),
+    Group(String),
+    Title(String),
+    Username(String),
+    Notes(String),
+    Password(String),
+    CreationTime(u32),
+    PasswordModTime(u32),
+    LastAccessTime(u32),
+    PasswordExpiryTime(u32),
+    LastModTime(u32),
+    Url(String),
+    Autotype(String),
+    PasswordHistory(String),
+    PasswordPolicy(String),
+    PasswordExpiryInterval(u32),
+    RunCommand(String),
+    DoubleClickAction(u16),
+    EMailAddress(String),
+    ProtectedEntry(u8),
+    OwnSymbolsForPassword(String),
+    ShiftDoubleClickAction(u16),
+    PasswordPolicyName(String),
+    EntryKeyboardShortcut(u32),
+    TwoFactorKey(Vec<u8>),
+    CreditCardNumber(String),
+    CreditCardExpiration(String),
+    CreditCardVerifValue(String),
+    CreditCardPin(String),
+    QRCode(String),
+    TotpConfig(u8),
+    TotpLength(u8),
+    TotpTimeStep(u8),
+    TotpStartTime(u32),
+    // reserved and implementation specific fields are kept as they are
+    Unknown(u8, Vec<u8>),
+}
+
+impl RecordField {
+    pub fn from_raw(kind: u8, bytes: &[u8]) -> Self {
+        // text that is not UTF-8 stays raw, a save writes it back unchanged
+        Self::parse(kind, bytes).unwrap_or_else(|| RecordField::Unknown(kind, bytes.to_vec()))
+    }
+
+    fn parse(kind: u8, bytes: &[u8]) -> Option<Self> {
+        use RecordField::*;
+        Some(match kind {
+            0x01 if bytes.len() == 16 => Uuid(uuid::Uuid::from_slice(bytes).unwrap_or_default()),
+            0x02 => Group(text(bytes)?),
+            0x03 => Title(text(bytes)?),
+            0x04 => Username(text(bytes)?),
+            0x05 => Notes(text(bytes)?),
+            0x06 => Password(text(bytes)?),
+            0x07 if bytes.len() >= 4 => CreationTime(int32(bytes)),
+            0x08 if bytes.len() >= 4 => PasswordModTime(int32(bytes)),
+            0x09 if bytes.len() >= 4 => LastAccessTime(int32(bytes)),
+            0x0a if bytes.len() >= 4 => PasswordExpiryTime(int32(bytes)),
+            0x0c if bytes.len() >= 4 => LastModTime(int32(bytes)),
+            0x0d => Url(text(bytes)?),
+            0x0e => Autotype(text(bytes)?),
+            0x0f => PasswordHistory(text(bytes)?),
+            0x10 => PasswordPolicy(text(bytes)?),
+            0x11 if bytes.len() >= 4 => PasswordExpiryInterval(int32(bytes)),
+            0x12 => RunCommand(text(bytes)?),
+            0x13 if bytes.len() >= 2 => DoubleClickAction(int16(bytes)),
+            0x14 => EMailAddress(text(bytes)?),
+            0x15 if !bytes.is_empty() => ProtectedEntry(bytes[0]),
+            0x16 => OwnSymbolsForPassword(text(bytes)?),
+            0x17 if bytes.len() >= 2 => ShiftDoubleClickAction(int16(bytes)),
+            0x18 => PasswordPolicyName(text(bytes)?),
+            0x19 if bytes.len() >= 4 => EntryKeyboardShortcut(int32(bytes)),
+            0x1b => TwoFactorKey(bytes.to_vec()),
+            0x1c => CreditCardNumber(text(bytes)?),
+            0x1d => CreditCardExpiration(text(bytes)?),
+            0x1e => CreditCardVerifValue(text(bytes)?),
+            0x1f => CreditCardPin(text(bytes)?),
+            0x20 => QRCode(text(bytes)?),
+            0x21 if !bytes.is_empty() => TotpConfig(bytes[0]),
+            0x22 if !bytes.is_empty() => TotpLength(bytes[0]),
+            0x23 if !bytes.is_empty() => TotpTimeStep(bytes[0]),
+            0x24 if bytes.len() >= 4 => TotpStartTime(int32(bytes)),
+            _ => return None,
+        })
+    }
+
+    pub fn kind(&self) -> u8 {
+        use RecordField::*;
+        match self {
+            Uuid(_) => 0x01,
+            Group(_) => 0x02,
+            Title(_) => 0x03,
+            Username(_) => 0x04,
+            Notes(_) => 0x05,
+            Password(_) => 0x06,
+            CreationTime(_) => 0x07,
+            PasswordModTime(_) => 0x08,
+            LastAccessTime(_) => 0x09,
+            PasswordExpiryTime(_) => 0x0a,
+            LastModTime(_) => 0x0c,
+            Url(_) => 0x0d,
+            Autotype(_) => 0x0e,
+            PasswordHistory(_) => 0x0f,
+            PasswordPolicy(_) => 0x10,
+            PasswordExpiryInterval(_) => 0x11,
+            RunCommand(_) => 0x12,
+            DoubleClickAction(_) => 0x13,
+            EMailAddress(_) => 0x14,
+            ProtectedEntry(_) => 0x15,
+            OwnSymbolsForPassword(_) => 0x16,
+            ShiftDoubleClickAction(_) => 0x17,
+            PasswordPolicyName(_) => 0x18,
+            EntryKeyboardShortcut(_) => 0x19,
+            TwoFactorKey(_) => 0x1b,
+            CreditCardNumber(_) => 0x1c,
+            CreditCardExpiration(_) => 0x1d,
+            CreditCardVerifValue(_) => 0x1e,
+            CreditCardPin(_) => 0x1f,
+            QRCode(_) => 0x20,
+            TotpConfig(_) => 0x21,
+            TotpLength(_) => 0x22,
+            TotpTimeStep(_) => 0x23,
+            TotpStartTime(_) => 0x24,
+            Unknown(kind, _) => *kind,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        use RecordField::*;
+        match self {
+            Uuid(u) => u.as_bytes().to_vec(),
+            Group(s)
+            | Title(s)
+            | Username(s)
+            | Notes(s)
+            | Password(s)
+            | Url(s)
+            | Autotype(s)
+            | PasswordHistory(s)
+            | PasswordPolicy(s)
+            | RunCommand(s)
+            | EMailAddress(s)
+            | OwnSymbolsForPassword(s)
+            | PasswordPolicyName(s)
+            | CreditCardNumber(s)
+            | CreditCardExpiration(s)
+            | CreditCardVerifValue(s)
+            | CreditCardPin(s)
+            | QRCode(s) => s.as_bytes().to_vec(),
+            CreationTime(t)
+            | PasswordModTime(t)
+            | LastAccessTime(t)
+            | PasswordExpiryTime(t)
+            | LastModTime(t)
+            | PasswordExpiryInterval(t)
+            | EntryKeyboardShortcut(t)
+            | TotpStartTime(t) => t.to_le_bytes().to_vec(),
+            DoubleClickAction(a) | ShiftDoubleClickAction(a) => a.to_le_bytes().to_vec(),
+            ProtectedEntry(b) | TotpConfig(b) | TotpLength(b) | TotpTimeStep(b) => vec![*b],
+            TwoFactorKey(bytes) | Unknown(_, bytes) => bytes.clone(),
+        }
+    }
+}
+
+impl Drop for RecordField {
+    // wipe the decrypted value before its memory is released
+    fn drop(&mut self) {
+        use RecordField::*;
+        match self {
+            Group(s)
+            | Title(s)
+            | Username(s)
+            | Notes(s)
+            | Password(s)
+            | Url(s)
+            | Autotype(s)
+            | PasswordHistory(s)
+            | PasswordPolicy(s)
+            | RunCommand(s)
+            | EMailAddress(s)
+            | OwnSymbolsForPassword(s)
+            | PasswordPolicyName(s)
+            | CreditCardNumber(s)
+            | CreditCardExpiration(s)
+            | CreditCardVerifValue(s)
+            | CreditCardPin(s)
+            | QRCode(s) => s.zeroize(),
+            TwoFactorKey(bytes) | Unknown(_, bytes) => bytes.zeroize(),
+            _ => {}
+        }
+    }
+}
+
+/// A single entry of the safe, a list of fields terminated by an end-of-entry field on disk
+#[derive(Default)]
+pub struct DbRecord {
+    pub fields: Vec<RecordField>,
+}
+
+macro_rules! text_field {
+    ($name:ident, $var:path) => {
+        pub fn $name(&self) -> Option<&str> {
+            self.fields.iter().find_map(|f| match f {
+                $var(s) => Some(s.as_str()),
+                _ => None,
+            })
+        }
+    };
+}
+
+macro_rules! int_field {
+    ($name:ident, $var:path) => {
+        pub fn $name(&self) -> Option<u32> {
+            self.fields.iter().find_map(|f| match f {
+                $var(t) if *t != 0 => Some(*t),
+                _ => None,
+            })
+        }
+    };
+}
+
+macro_rules! byte_field {
+    ($name:ident, $var:path) => {
+        pub fn $name(&self) -> Option<u8> {
+            self.fields.iter().find_map(|f| match f {
+                $var(b) => Some(*b),
+                _ => None,
+            })
+        }
+    };
+}
+
+impl Zeroize for DbRecord {
+    // every field wipes its value when dropped
+    fn zeroize(&mut self) {
+        self.fields.clear();
+    }
+}
+
+/// Passwords kept by pwsafe when the password changes, oldest first
+pub struct PasswordHistory {
+    pub enabled: bool,
+    pub max: usize,
+    pub entries: Vec<(u32, String)>,
+}
+
+impl DbRecord {
+    text_field!(group, RecordField::Group);
+    text_field!(title, RecordField::Title);
+    text_field!(username, RecordField::Username);
+    text_field!(password, RecordField::Password);
+    text_field!(url, RecordField::Url);
+    text_field!(notes, RecordField::Notes);
+    text_field!(email, RecordField::EMailAddress);
+    text_field!(autotype, RecordField::Autotype);
+    text_field!(run_command, RecordField::RunCommand);
+    text_field!(password_policy, RecordField::PasswordPolicy);
+    text_field!(password_policy_name, RecordField::PasswordPolicyName);
+    text_field!(own_symbols, RecordField::OwnSymbolsForPassword);
+    int_field!(creation_time, RecordField::CreationTime);
+    int_field!(password_mod_time, RecordField::PasswordModTime);
+    int_field!(last_access_time, RecordField::LastAccessTime);
+    int_field!(last_mod_time, RecordField::LastModTime);
+    int_field!(password_expiry_time, RecordField::PasswordExpiryTime);
+    int_field!(
+        password_expiry_interval,
+        RecordField::PasswordExpiryInterval
+    );
+    int_field!(keyboard_shortcut, RecordField::EntryKeyboardShortcut);
+    int_field!(totp_start_time, RecordField::TotpStartTime);
+    byte_field!(totp_config, RecordField::TotpConfig);
+    byte_field!(totp_length, RecordField::TotpLength);
+    byte_field!(totp_time_step, RecordField::TotpTimeStep);
+
+    /// Raw secret of the one-time codes, not the base32 text of authenticator apps
+    pub fn two_factor_key(&self) -> Option<&[u8]> {
+        self.fields.iter().find_map(|f| match f {
+            RecordField::TwoFactorKey(key) if !key.is_empty() => Some(key.as_slice()),
+            _ => None,
+        })
+    }
+
+    /// Parsed password history, `fmmnn` followed by nn times `TTTTTTTTLLLLpassword`
+    pub fn password_history(&self) -> Option<PasswordHistory> {
+        let raw = self.fields.iter().find_map(|f| match f {
+            RecordField::PasswordHistory(s) => Some(s.as_str()),
+            _ => None,
+        })?;
+        let hex = |s: Option<&str>| s.and_then(|s| usize::from_str_radix(s, 16).ok());
+        let mut history = PasswordHistory {
+            enabled: raw.get(..1)? != "0",
+            max: hex(raw.get(1..3))?,
+            entries: Vec::new(),
+        };
+        let count = hex(raw.get(3..5))?;
+        let mut rest = raw.get(5..)?;
+        for _ in 0..count {
+            let time = hex(rest.get(..8))? as u32;
+            let len = hex(rest.get(8..12))?;
+            // the length counts characters, not bytes
+            let end = rest
+                .char_indices()
+                .map(|(i, _)| i)
+                .chain([rest.len()])
+                .nth(12 + len)?;
+            history.entries.push((time, rest.get(12..end)?.to_string()));
+            rest = &rest[end..];
+        }
+        Some(history)
+    }
+
+    /// When the password expires, the expiry time or the interval counted from the last change
+    pub fn expiry(&self) -> Option<u32> {
+        self.password_expiry_time().or_else(|| {
+            let days = self.password_expiry_interval()?;
+            let changed = self.password_mod_time().or_else(|| self.creation_time())?;
+            Some(changed.saturating_add(days.saturating_mul(86_400)))
+        })
+    }
+
+    pub fn uuid(&self) -> Option<Uuid> {
+        self.fields.iter().find_map(|f| match f {
+            RecordField::Uuid(u) => Some(*u),
+            _ => None,
+        })
+    }
+
+    /// Replaces the field of the same kind or appends it
+    pub fn set(&mut self, field: RecordField) {
+        match self.fields.iter_mut().find(|f| f.kind() == field.kind()) {
+            Some(existing) => *existing = field,
+            None => self.fields.push(field),
+        }
+    }
+
+    // field by field through the bytes of the file, records are copied by `Secret::copy_of`
+    pub(super) fn duplicate(&self) -> DbRecord {
+        let fields = self
+            .fields
+            .iter()
+            .map(|f| RecordField::from_raw(f.kind(), &Zeroizing::new(f.to_bytes())))
+            .collect();
+        DbRecord { fields }
+    }
+
+    /// Removes all fields of the given kind
+    pub fn remove(&mut self, kind: u8) {
+        self.fields.retain(|f| f.kind() != kind);
+    }
+
+    /// Lines of the notes, pwsafe stores notes with windows line endings
+    pub fn note_lines(&self) -> impl Iterator<Item = &str> {
+        self.notes()
+            .filter(|n| !n.is_empty())
+            .into_iter()
+            .flat_map(|n| n.split('\n'))
+            .map(|line| line.strip_suffix('\r').unwrap_or(line))
+    }
+
+    /// Notes with `\n` line breaks, stored with the line endings of pwsafe, empty notes are removed
+    pub fn set_notes(&mut self, notes: &str) {
+        match notes {
+            "" => self.remove(RecordField::Notes(String::new()).kind()),
+            _ => self.set(RecordField::Notes(notes.replace('\n', "\r\n"))),
+        }
+    }
+}
+
+pub(super) fn text(bytes: &[u8]) -> Option<String> {
+    std::str::from_utf8(bytes).ok().map(str::to_string)
+}
+
+pub(super) fn int32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+pub(super) fn int16(bytes: &[u8]) -> u16 {
+    u16::from_le_bytes([bytes[0], bytes[1]])
+}
@@ -0,0 +1,85 @@
+use crate::model::fuzzy::fuzzy_match;
+use crate::model::record::DbRecord;
+use uuid::Uuid;
+
+// a term found in the title ranks above the same term in the notes
+const TITLE_WEIGHT: i64 = 30;
+const GROUP_WEIGHT: i64 = 15;
+const ACCOUNT_WEIGHT: i64 = 10;
+
+/// A record matching every term of a search, with the matched characters to highlight
+pub struct SearchHit {
+    pub uuid: Option<Uuid>,
+    pub title: String,
+    pub group: Option<String>,
+    pub score: i64,
+    pub title_positions: Vec<usize>,
+    pub group_positions: Vec<usize>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Place {
+    Title,
+    Group,
+    Other,
+}
+
+/// Rank the records by how well they match all whitespace separated terms of the query
+pub fn search<'a>(records: impl Iterator<Item = &'a DbRecord>, query: &str) -> Vec<SearchHit> {
+    let terms: Vec<&str> = query.split_whitespace().collect();
+    if terms.is_empty() {
+        return Vec::new();
+    }
+    let mut hits: Vec<SearchHit> = records
+        .filter_map(|record| match_record(record, &terms))
+        .collect();
+    hits.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.title.cmp(&b.title)));
+    hits
+}
+
+fn match_record(record: &DbRecord, terms: &[&str]) -> Option<SearchHit> {
+    let title = record.title().unwrap_or_default();
+    let group = record.group().unwrap_or_default();
+    let fields = [
+        (Place::Title, title, TITLE_WEIGHT),
+        (Place::Group, group, GROUP_WEIGHT),
+        (
+            Place::Other,
+            record.username().unwrap_or_default(),
+            ACCOUNT_WEIGHT,
+        ),
+        (
+            Place::Other,
+            record.url().unwrap_or_default(),
+            ACCOUNT_WEIGHT,
+        ),
+        (Place::Other, record.notes().unwrap_or_default(), 0),
+    ];
+    let mut hit = SearchHit {
+        uuid: record.uuid(),
+        title: title.to_string(),
+        group: record.group().map(str::to_string),
+        score: 0,
+        title_positions: Vec::new(),
+        group_positions: Vec::new(),
+    };
+    for term in terms {
+        // every term has to match somewhere, the best field counts
+        let (place, found) = fields
+            .iter()
+            .filter_map(|(place, text, weight)| {
+                fuzzy_match(term, text).map(|mut m| {
+                    m.score += weight;
+                    (*place, m)
+                })
+            })
+            .max_by_key(|(_, m)| m.score)?;
+        hit.score += found.score;
+        match place {
+            Place::Title => hit.title_positions.extend(found.positions),
+            Place::Group => hit.group_positions.extend(found.positions),
+            Place::Other => {}
+        }
+    }
+    Some(hit)
+}
@@ -0,0 +1,165 @@
+//! Plaintext secrets outside of the safe, wiped when dropped and never printed or copied by accident
+
+use crate::model::record::DbRecord;
+use zeroize::Zeroize;
+
+/// Shown in place of a hidden password, the same for every password so it doesn't tell the length
+pub const MASK: &str = "********";
+
+/// Room for a typed passphrase before the buffer has to grow
+const INPUT_CAPACITY: usize = 64;
+
+/// A value that is zeroized on drop, it implements neither `Clone` nor `Debug`
+pub struct Secret<T: Zeroize> {
+    value: T,
+    // pages of a text buffer that are kept out of swap
+    locked: Option<memlock::Pages>,
+}
+
+impl<T: Zeroize> Secret<T> {
+    pub fn new(value: T) -> Self {
+        Secret {
+            value,
+            locked: None,
+        }
+    }
+
+    pub fn expose(&self) -> &T {
+        &self.value
+    }
+
+    /// Wipe the value now instead of when it is dropped
+    pub fn wipe(&mut self) {
+        self.value.zeroize();
+    }
+}
+
+impl Secret<String> {
+    /// Empty text to type into
+    pub fn input() -> Self {
+        Self::text(String::with_capacity(INPUT_CAPACITY))
+    }
+
+    pub fn text(value: String) -> Self {
+        let locked = memlock::lock(value.as_ptr(), value.capacity());
+        Secret { value, locked }
+    }
+
+    /// A deliberate copy, e.g. of a shown value for the clipboard
+    pub fn duplicate(&self) -> Self {
+        Self::text(self.value.clone())
+    }
+
+    /// Append a character, a buffer that has to grow is copied and the old one wiped
+    pub fn push(&mut self, c: char) {
+        if self.value.len() + c.len_utf8() > self.value.capacity() {
+            let capacity = (self.value.capacity() * 2).max(INPUT_CAPACITY);
+            let mut grown = String::with_capacity(capacity);
+            grown.push_str(&self.value);
+            let locked = memlock::lock(grown.as_ptr(), grown.capacity());
+            self.value.zeroize();
+            if let Some(pages) = std::mem::replace(&mut self.locked, locked) {
+                memlock::unlock(pages);
+            }
+            self.value = grown;
+        }
+        self.value.push(c);
+    }
+
+    pub fn pop(&mut self) {
+        if let Some(c) = self.value.pop() {
+            // the removed bytes would stay behind in the spare capacity
+            // SAFETY: only the bytes after the end of the string are written
+            let bytes = unsafe { self.value.as_mut_vec() };
+            bytes.spare_capacity_mut()[..c.len_utf8()].zeroize();
+        }
+    }
+}
+
+impl Secret<DbRecord> {
+    /// A deliberate copy of a record, e.g. of the entry that is shown or edited
+    pub fn copy_of(record: &DbRecord) -> Self {
+        Secret::new(record.duplicate())
+    }
+
+    /// Hand the record over, e.g. an edited copy to the model
+    pub fn take(&mut self) -> DbRecord {
+        std::mem::take(&mut self.value)
+    }
+}
+
+impl<T: Zeroize> Drop for Secret<T> {
+    fn drop(&mut self) {
+        self.value.zeroize();
+        if let Some(pages) = self.locked.take() {
+            memlock::unlock(pages);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod memlock {
+    use std::collections::BTreeMap;
+    use std::sync::Mutex;
+
+    // a page can hold several secrets, it is unlocked with the last one
+    static LOCKED: Mutex<BTreeMap<usize, usize>> = Mutex::new(BTreeMap::new());
+
+    pub struct Pages {
+        first: usize,
+        count: usize,
+    }
+
+    fn page_size() -> usize {
+        // SAFETY: sysconf has no preconditions
+        unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+    }
+
+    /// Keep the pages of a buffer out of swap, best effort as the limit of locked memory may be low
+    pub fn lock(ptr: *const u8, len: usize) -> Option<Pages> {
+        if len == 0 {
+            return None;
+        }
+        let size = page_size();
+        let first = ptr as usize / size;
+        let count = (ptr as usize + len - 1) / size - first + 1;
+        let mut locked = LOCKED.lock().ok()?;
+        // SAFETY: mlock only changes the paging of the mapped pages of the buffer
+        if unsafe { libc::mlock((first * size) as *const libc::c_void, count * size) } != 0 {
+            return None;
+        }
+        for page in first..first + count {
+            *locked.entry(page).or_default() += 1;
+        }
+        Some(Pages { first, count })
+    }
+
+    pub fn unlock(pages: Pages) {
+        let Ok(mut locked) = LOCKED.lock() else {
+            return;
+        };
+        let size = page_size();
+        for page in pages.first..pages.first + pages.count {
+            let Some(count) = locked.get_mut(&page) else {
+                continue;
+            };
+            *count -= 1;
+            if *count == 0 {
+                locked.remove(&page);
+                // SAFETY: see lock
+                unsafe { libc::munlock((page * size) as *const libc::c_void, size) };
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod memlock {
+    pub struct Pages;
+
+    pub fn lock(_: *const u8, _: usize) -> Option<Pages> {
+        None
+    }
+
+    pub fn unlock(_: Pages) {}
+}
@@ -0,0 +1,340 @@
+//! Time based one-time codes of RFC 6238 from the two-factor key and TOTP fields of an entry,
+//! and the `otpauth://` URIs authenticator apps set them up with
+
+use crate::contracts::TuiPwSafeErrors;
+use crate::contracts::TuiPwSafeErrors::InvalidOtpAuth;
+use crate::model::record::{DbRecord, RecordField};
+use crate::model::secret::Secret;
+use base32::Alphabet;
+use hmac::{Hmac, Mac};
+use percent_encoding::percent_decode_str;
+use sha1::Sha1;
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+type HmacSha1 = Hmac<Sha1>;
+
+// what pwsafe assumes for fields that are not set
+const DEFAULT_DIGITS: u32 = 6;
+const DEFAULT_TIME_STEP: u64 = 30;
+// the low bits of the config field, only HMAC-SHA1 is defined
+const ALGORITHM_MASK: u8 = 0x03;
+const HMAC_SHA1: u8 = 0;
+
+/// Key and parameters of the one-time codes of an entry
+pub struct Totp {
+    key: Secret<Vec<u8>>,
+    digits: u32,
+    time_step: u64,
+    start_time: u64,
+}
+
+/// Key and parameters of an `otpauth://totp/Issuer:account?secret=...` URI
+pub struct OtpAuth {
+    key: Secret<Vec<u8>>,
+    pub issuer: Option<String>,
+    pub account: String,
+    digits: u8,
+    time_step: u8,
+}
+
+/// The code of the current time step and the seconds it stays valid
+pub struct TotpCode {
+    pub code: Secret<String>,
+    pub remaining: u64,
+    pub time_step: u64,
+}
+
+impl Totp {
+    /// Parameters of the record, none without a two-factor key or with an unknown algorithm
+    pub fn from_record(record: &DbRecord) -> Option<Self> {
+        let key = record.two_factor_key()?;
+        let config = record.totp_config().unwrap_or(0);
+        if config & ALGORITHM_MASK != HMAC_SHA1 {
+            return None;
+        }
+        Some(Totp {
+            key: Secret::new(key.to_vec()),
+            digits: record
+                .totp_length()
+                .map(u32::from)
+                .filter(|d| (6..=8).contains(d))
+                .unwrap_or(DEFAULT_DIGITS),
+            time_step: record
+                .totp_time_step()
+                .map(u64::from)
+                .filter(|&s| s > 0)
+                .unwrap_or(DEFAULT_TIME_STEP),
+            start_time: record.totp_start_time().map_or(0, u64::from),
+        })
+    }
+
+    pub fn code(&self) -> TotpCode {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        self.code_at(now)
+    }
+
+    /// HOTP of RFC 4226 over the number of time steps since the start time
+    pub fn code_at(&self, time: u64) -> TotpCode {
+        let elapsed = time.saturating_sub(self.start_time);
+        let counter = elapsed / self.time_step;
+        let mut mac =
+            HmacSha1::new_from_slice(self.key.expose()).expect("HMAC accepts any key size");
+        mac.update(&counter.to_be_bytes());
+        let hash = mac.finalize().into_bytes();
+        // dynamic truncation, the low nibble of the last byte is the offset
+        let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+        let value = u32::from_be_bytes([
+            hash[offset] & 0x7f,
+            hash[offset + 1],
+            hash[offset + 2],
+            hash[offset + 3],
+        ]);
+        TotpCode {
+            code: Secret::text(format!(
+                "{:0width$}",
+                value % 10u32.pow(self.digits),
+                width = self.digits as usize
+            )),
+            remaining: self.time_step - elapsed % self.time_step,
+            time_step: self.time_step,
+        }
+    }
+}
+
+impl OtpAuth {
+    /// A URI, or a file with the URI on one of its lines, e.g. the output of a QR code reader
+    pub fn read(input: &str) -> Result<Self, TuiPwSafeErrors> {
+        let input = input.trim();
+        let is_uri = |line: &str| {
+            line.trim_start()
+                .get(..8)
+                .is_some_and(|s| s.eq_ignore_ascii_case("otpauth:"))
+        };
+        if is_uri(input) {
+            return Self::parse(input);
+        }
+        let text = fs::read_to_string(input)
+            .map(Secret::text)
+            .map_err(|e| InvalidOtpAuth(format!("{}: {}", input, e)))?;
+        let uri = text
+            .expose()
+            .lines()
+            .find(|line| is_uri(line))
+            .ok_or_else(|| InvalidOtpAuth(format!("{} has no otpauth URI", input)))?;
+        Self::parse(uri)
+    }
+
+    /// The URI of a QR code, only SHA1 and time steps of up to 255 seconds fit into the safe
+    pub fn parse(uri: &str) -> Result<Self, TuiPwSafeErrors> {
+        let invalid = |problem: &str| InvalidOtpAuth(problem.to_string());
+        let uri = uri.trim();
+        let rest = uri
+            .get(..10)
+            .filter(|scheme| scheme.eq_ignore_ascii_case("otpauth://"))
+            .map(|_| &uri[10..])
+            .ok_or_else(|| invalid("it has to start with otpauth://"))?;
+        let (kind, rest) = rest.split_once('/').unwrap_or((rest, ""));
+        match kind.to_ascii_lowercase().as_str() {
+            "totp" => {}
+            "hotp" => return Err(invalid("counter based hotp codes are not supported")),
+            _ => return Err(invalid("the type has to be totp")),
+        }
+        let (label, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let label = decoded(label);
+        let (mut issuer, account) = match label.split_once(':') {
+            Some((issuer, account)) => (Some(issuer.trim().to_string()), account.trim()),
+            None => (None, label.trim()),
+        };
+        let mut otp = OtpAuth {
+            key: Secret::new(Vec::new()),
+            issuer: None,
+            account: account.to_string(),
+            digits: DEFAULT_DIGITS as u8,
+            time_step: DEFAULT_TIME_STEP as u8,
+        };
+        for (name, value) in query.split('&').filter_map(|p| p.split_once('=')) {
+            let value = decoded(value);
+            match name.to_ascii_lowercase().as_str() {
+                "secret" => {
+                    // authenticator apps leave out the padding and may group the characters
+                    let secret: String = value
+                        .chars()
+                        .filter(|c| !c.is_whitespace() && *c != '=')
+                        .collect::<String>()
+                        .to_ascii_uppercase();
+                    let key = base32::decode(Alphabet::Rfc4648 { padding: false }, &secret)
+                        .ok_or_else(|| invalid("the secret is not base32"))?;
+                    otp.key = Secret::new(key);
+                }
+                // the parameter wins over the label
+                "issuer" => issuer = Some(value).filter(|v| !v.is_empty()),
+                "algorithm" => match value.to_ascii_uppercase().as_str() {
+                    "SHA1" => {}
+                    "SHA256" | "SHA512" => {
+                        return Err(invalid("Password Safe only keeps SHA1 keys"))
+                    }
+                    _ => return Err(invalid("unknown algorithm")),
+                },
+                "digits" => {
+                    otp.digits = value
+                        .parse()
+                        .ok()
+                        .filter(|d| (6..=8).contains(d))
+                        .ok_or_else(|| invalid("the digits have to be between 6 and 8"))?
+                }
+                "period" => {
+                    otp.time_step = value
+                        .parse()
+                        .ok()
+                        .filter(|&p| p > 0)
+                        .ok_or_else(|| invalid("the period has to be between 1 and 255"))?
+                }
+                _ => {}
+            }
+        }
+        if otp.key.expose().is_empty() {
+            return Err(invalid("the secret is missing"));
+        }
+        otp.issuer = issuer;
+        Ok(otp)
+    }
+
+    /// Issuer and account for messages, e.g. `GitHub (alice)`
+    pub fn name(&self) -> String {
+        match (&self.issuer, self.account.is_empty()) {
+            (Some(issuer), false) => format!("{} ({})", issuer, self.account),
+            (Some(issuer), true) => issuer.clone(),
+            (None, _) => self.account.clone(),
+        }
+    }
+
+    /// Store the key in the two-factor fields of the record, replacing an earlier key
+    pub fn apply(&self, record: &mut DbRecord) {
+        record.set(RecordField::TwoFactorKey(self.key.expose().clone()));
+        record.set(RecordField::TotpConfig(HMAC_SHA1));
+        record.set(RecordField::TotpLength(self.digits));
+        record.set(RecordField::TotpTimeStep(self.time_step));
+        // codes of the URI count from the epoch
+        record.remove(RecordField::TotpStartTime(0).kind());
+    }
+}
+
+fn decoded(text: &str) -> String {
+    percent_decode_str(text).decode_utf8_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the SHA1 seed of the test vectors in appendix B of RFC 6238
+    const SEED: &[u8] = b"12345678901234567890";
+
+    fn totp(digits: u8) -> Totp {
+        let mut record = DbRecord::default();
+        record.set(RecordField::TwoFactorKey(SEED.to_vec()));
+        record.set(RecordField::TotpLength(digits));
+        record.set(RecordField::TotpTimeStep(30));
+        Totp::from_record(&record).expect("a SHA1 key")
+    }
+
+    #[test]
+    fn rfc_6238_sha1_vectors() {
+        let totp = totp(8);
+        for (time, code) in [
+            (59, "94287082"),
+            (1_111_111_109, "07081804"),
+            (1_111_111_111, "14050471"),
+            (1_234_567_890, "89005924"),
+            (2_000_000_000, "69279037"),
+            (20_000_000_000, "65353130"),
+        ] {
+            assert_eq!(totp.code_at(time).code.expose(), code, "at {}", time);
+        }
+    }
+
+    #[test]
+    fn six_digits_and_remaining_seconds() {
+        let code = totp(6).code_at(59);
+        assert_eq!(code.code.expose(), "287082");
+        assert_eq!(code.remaining, 1);
+        assert_eq!(code.time_step, 30);
+        assert_eq!(totp(6).code_at(60).remaining, 30);
+    }
+
+    fn parse(uri: &str) -> OtpAuth {
+        OtpAuth::parse(uri).unwrap_or_else(|e| panic!("{}: {}", uri, e))
+    }
+
+    fn problem(uri: &str) -> String {
+        match OtpAuth::parse(uri) {
+            Err(InvalidOtpAuth(problem)) => problem,
+            Err(e) => panic!("{}: {}", uri, e),
+            Ok(_) => panic!("{} was accepted", uri),
+        }
+    }
+
+    #[test]
+    fn otpauth_uri() {
+        let otp = parse(
+            "otpauth://totp/ACME%20Co:alice@example.com?secret=JBSW%20Y3DP%20EHPK%203PXP&issuer=ACME",
+        );
+        assert_eq!(otp.key.expose()[..], b"Hello!\xde\xad\xbe\xef"[..]);
+        assert_eq!(otp.issuer.as_deref(), Some("ACME"));
+        assert_eq!(otp.account, "alice@example.com");
+        assert_eq!(otp.name(), "ACME (alice@example.com)");
+        assert_eq!(otp.digits, 6);
+        assert_eq!(otp.time_step, 30);
+
+        let otp =
+            parse("OTPAUTH://TOTP/ACME%20Co:alice?secret=jbswy3dpehpk3pxp===&digits=8&period=60");
+        assert_eq!(otp.issuer.as_deref(), Some("ACME Co"));
+        assert_eq!(otp.digits, 8);
+        assert_eq!(otp.time_step, 60);
+
+        let mut record = DbRecord::default();
+        record.set(RecordField::TotpStartTime(100));
+        otp.apply(&mut record);
+        assert_eq!(record.totp_length(), Some(8));
+        assert_eq!(record.totp_time_step(), Some(60));
+        assert_eq!(record.totp_start_time(), None);
+        assert_eq!(Totp::from_record(&record).map(|t| t.digits), Some(8));
+    }
+
+    #[test]
+    fn invalid_otpauth_uri() {
+        assert_eq!(problem("otpauth://totp/alice"), "the secret is missing");
+        assert_eq!(
+            problem("otpauth://totp/alice?secret="),
+            "the secret is missing"
+        );
+        assert_eq!(
+            problem("otpauth://totp/alice?secret=JBSW1"),
+            "the secret is not base32"
+        );
+        for digits in ["5", "9", "six"] {
+            let uri = format!("otpauth://totp/alice?secret=JBSWY3DP&digits={}", digits);
+            assert_eq!(problem(&uri), "the digits have to be between 6 and 8");
+        }
+        for period in ["0", "256", "-30"] {
+            let uri = format!("otpauth://totp/alice?secret=JBSWY3DP&period={}", period);
+            assert_eq!(problem(&uri), "the period has to be between 1 and 255");
+        }
+        assert_eq!(
+            problem("otpauth://totp/alice?secret=JBSWY3DP&algorithm=SHA256"),
+            "Password Safe only keeps SHA1 keys"
+        );
+        assert_eq!(
+            problem("otpauth://hotp/alice?secret=JBSWY3DP&counter=1"),
+            "counter based hotp codes are not supported"
+        );
+        assert_eq!(
+            problem("https://example.com"),
+            "it has to start with otpauth://"
+        );
+    }
+}
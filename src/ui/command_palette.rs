@@ -0,0 +1,187 @@
+use crate::keymap::{Action, KeyMap};
+use crate::model::fuzzy::fuzzy_match;
+use crate::ui::centered_rect;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::cmp::Reverse;
+use tui::backend::Backend;
+use tui::layout::{Constraint, Direction, Layout, Rect};
+use tui::style::{Color, Modifier, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph};
+use tui::Frame;
+
+const HELP_TEXT: &str = "Type to filter, <Up>/<Down> select, <Enter> run, <Esc> close";
+
+/// A command of the palette, matched by its description and its config name
+struct Command {
+    action: Action,
+    keys: String,
+    score: i64,
+    positions: Vec<usize>,
+}
+
+/// Overlay listing the commands of the active view with their keys, filtered by fuzzy matching
+pub struct CommandPalette {
+    query: String,
+    commands: Vec<Action>,
+    keys: KeyMap,
+    matches: Vec<Command>,
+    state: ListState,
+    chosen: Option<Action>,
+    closed: bool,
+}
+
+impl CommandPalette {
+    pub fn new(commands: Vec<Action>, keys: KeyMap) -> Self {
+        let mut palette = CommandPalette {
+            query: String::new(),
+            commands,
+            keys,
+            matches: Vec::new(),
+            state: ListState::default(),
+            chosen: None,
+            closed: false,
+        };
+        palette.filter();
+        palette
+    }
+
+    pub fn capture_key(&mut self, key: KeyEvent) {
+        let len = self.matches.len();
+        match key.code {
+            KeyCode::Esc => self.closed = true,
+            KeyCode::Enter => {
+                self.chosen = self.state.selected().map(|i| self.matches[i].action);
+                self.closed = true;
+            }
+            KeyCode::Up | KeyCode::BackTab if len > 0 => {
+                let row = self.state.selected().map_or(0, |r| (r + len - 1) % len);
+                self.state.select(Some(row));
+            }
+            KeyCode::Down | KeyCode::Tab if len > 0 => {
+                let row = self.state.selected().map_or(0, |r| (r + 1) % len);
+                self.state.select(Some(row));
+            }
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.filter();
+            }
+            KeyCode::Char(c)
+                if !key
+                    .modifiers
+                    .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+            {
+                self.query.push(c);
+                self.filter();
+            }
+            _ => {}
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// The command to run once the palette is closed with <Enter>
+    pub fn chosen(&self) -> Option<Action> {
+        self.chosen
+    }
+
+    pub fn draw<B: Backend>(&mut self, f: &mut Frame<B>, rec: Rect) {
+        let popup = centered_rect(60, 60, rec);
+        f.render_widget(Clear, popup);
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3),
+                Constraint::Min(3),
+                Constraint::Length(1),
+            ])
+            .split(popup);
+
+        let input = Paragraph::new(self.query.as_str())
+            .block(Block::default().title("Command").borders(Borders::ALL));
+        f.render_widget(input, chunks[0]);
+
+        let text = Style::default().fg(Color::White).bg(Color::Black);
+        let matched = text.fg(Color::Yellow).add_modifier(Modifier::BOLD);
+        let width = chunks[1].width.saturating_sub(7) as usize;
+        let items: Vec<ListItem> = self
+            .matches
+            .iter()
+            .map(|command| {
+                let description = command.action.description();
+                let mut spans: Vec<Span> = description
+                    .chars()
+                    .enumerate()
+                    .map(|(i, c)| {
+                        let style = if command.positions.contains(&i) {
+                            matched
+                        } else {
+                            text
+                        };
+                        Span::styled(c.to_string(), style)
+                    })
+                    .collect();
+                // the keys are right aligned
+                let gap = width.saturating_sub(description.chars().count() + command.keys.len());
+                spans.push(Span::styled(
+                    format!("{}{}", " ".repeat(gap.max(1)), command.keys),
+                    text.fg(Color::Gray),
+                ));
+                ListItem::new(Spans::from(spans)).style(text)
+            })
+            .collect();
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .title(format!("Commands ({})", self.matches.len()))
+                    .borders(Borders::ALL),
+            )
+            .highlight_style(
+                Style::default()
+                    .fg(Color::LightGreen)
+                    .add_modifier(Modifier::BOLD),
+            )
+            .highlight_symbol(">> ");
+        f.render_stateful_widget(list, chunks[1], &mut self.state);
+
+        let help =
+            Paragraph::new(HELP_TEXT).style(Style::default().bg(Color::Black).fg(Color::Gray));
+        f.render_widget(help, chunks[2]);
+        f.set_cursor(chunks[0].x + self.query.len() as u16 + 1, chunks[0].y + 1);
+    }
+
+    /// Rank the commands by the query, all of them in their usual order without one
+    fn filter(&mut self) {
+        let query = self.query.trim();
+        self.matches = self
+            .commands
+            .iter()
+            .filter_map(|&action| {
+                let keys = self.keys.keys(action);
+                if query.is_empty() {
+                    return Some(Command {
+                        action,
+                        keys,
+                        score: 0,
+                        positions: Vec::new(),
+                    });
+                }
+                let by_description = fuzzy_match(query, action.description());
+                // the config name only ranks, its positions are not shown
+                let by_name = fuzzy_match(query, action.name()).map(|m| m.score);
+                let score = by_description.as_ref().map(|m| m.score).max(by_name)?;
+                Some(Command {
+                    action,
+                    keys,
+                    score,
+                    positions: by_description.map(|m| m.positions).unwrap_or_default(),
+                })
+            })
+            .collect();
+        // stable, equal scores keep the order of the commands
+        self.matches.sort_by_key(|c| Reverse(c.score));
+        self.state.select((!self.matches.is_empty()).then_some(0));
+    }
+}
@@ -0,0 +1,145 @@
+use crate::model::policy::PasswordPolicy;
+use crate::model::record::DbRecord;
+use crate::model::secret::MASK;
+use crate::model::{format_time, now};
+use std::borrow::Cow;
+use std::time::Duration;
+use tui::text::{Span, Spans};
+
+// modifier bits of the keyboard shortcut as pwsafe stores them
+const SHORTCUT_MODIFIERS: [(u32, &str); 4] = [
+    (0x02, "Ctrl"),
+    (0x01, "Alt"),
+    (0x04, "Shift"),
+    (0x10, "Meta"),
+];
+
+/// All fields of a record for the entry pane, fields that are not set are left out.
+/// The password is only shown while `reveal` has time left. The lines borrow the values
+/// of the record, so that no copy of them outlives the frame without being wiped.
+pub fn entry_lines(entry: &DbRecord, reveal: Option<Duration>) -> Vec<Spans<'_>> {
+    let mut lines = Vec::new();
+    row(&mut lines, "title", entry.title());
+    row(&mut lines, "group", entry.group());
+    row(&mut lines, "username", entry.username());
+    if let Some(password) = entry.password().filter(|p| !p.is_empty()) {
+        let mut spans = vec![Span::raw(format!("{:12}: ", "password"))];
+        match reveal {
+            Some(left) => {
+                spans.push(Span::raw(password));
+                spans.push(Span::raw(format!("  (hidden in {}s)", left.as_secs() + 1)));
+            }
+            None => spans.push(Span::raw(MASK)),
+        }
+        lines.push(Spans::from(spans));
+    }
+    row(&mut lines, "url", entry.url());
+    row(&mut lines, "email", entry.email());
+    row(
+        &mut lines,
+        "created",
+        entry.creation_time().map(format_time),
+    );
+    row(
+        &mut lines,
+        "modified",
+        entry.last_mod_time().map(format_time),
+    );
+    row(
+        &mut lines,
+        "password set",
+        entry.password_mod_time().map(format_time),
+    );
+    row(
+        &mut lines,
+        "accessed",
+        entry.last_access_time().map(format_time),
+    );
+    row(&mut lines, "expires", expiry(entry));
+    row(&mut lines, "autotype", entry.autotype());
+    row(&mut lines, "run command", entry.run_command());
+    row(
+        &mut lines,
+        "shortcut",
+        entry.keyboard_shortcut().map(shortcut),
+    );
+    row(
+        &mut lines,
+        "history",
+        entry.password_history().map(|h| {
+            let state = if h.enabled { "on" } else { "off" };
+            format!(
+                "{} old passwords, keeps {}, {}",
+                h.entries.len(),
+                h.max,
+                state
+            )
+        }),
+    );
+    row(&mut lines, "policy", policy(entry));
+
+    for (i, line) in entry.note_lines().enumerate() {
+        let label = if i == 0 { "notes:" } else { "" };
+        lines.push(Spans::from(vec![
+            Span::raw(format!("{:13} ", label)),
+            Span::raw(line),
+        ]));
+    }
+    lines
+}
+
+fn row<'a>(lines: &mut Vec<Spans<'a>>, label: &str, value: Option<impl Into<Cow<'a, str>>>) {
+    let value = value.map(Into::into).filter(|v| !v.is_empty());
+    if let Some(value) = value {
+        lines.push(Spans::from(vec![
+            Span::raw(format!("{:12}: ", label)),
+            Span::raw(value),
+        ]));
+    }
+}
+
+fn expiry(entry: &DbRecord) -> Option<String> {
+    let expired = entry.expiry().is_some_and(|expiry| expiry <= now());
+    let text = match (
+        entry.password_expiry_time(),
+        entry.password_expiry_interval(),
+    ) {
+        (Some(time), Some(days)) => Some(format!("{} (every {} days)", format_time(time), days)),
+        (Some(time), None) => Some(format_time(time)),
+        (None, Some(days)) => Some(format!("every {} days", days)),
+        (None, None) => None,
+    }?;
+    Some(if expired {
+        format!("{}, expired", text)
+    } else {
+        text
+    })
+}
+
+fn policy(entry: &DbRecord) -> Option<String> {
+    let policy = match entry.password_policy_name() {
+        Some(name) => format!("named policy {}", name),
+        None => PasswordPolicy::parse(entry.password_policy()?)?.describe(),
+    };
+    Some(match entry.own_symbols() {
+        Some(symbols) => format!("{}, symbols {}", policy, symbols),
+        None => policy,
+    })
+}
+
+/// Virtual key code in the low word, modifiers in the third byte
+fn shortcut(value: u32) -> String {
+    let key = value & 0xffff;
+    let modifiers = (value >> 16) & 0xff;
+    let mut parts: Vec<String> = SHORTCUT_MODIFIERS
+        .iter()
+        .filter(|(bit, _)| modifiers & bit != 0)
+        .map(|(_, name)| name.to_string())
+        .collect();
+    parts.push(match key {
+        0x30..=0x39 | 0x41..=0x5a => char::from(key as u8).to_string(),
+        0x70..=0x87 => format!("F{}", key - 0x6f),
+        _ => format!("key {:#04x}", key),
+    });
+    parts.join("+")
+}
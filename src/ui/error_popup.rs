@@ -0,0 +1,37 @@
+use crate::ui::centered_rect;
+use tui::backend::Backend;
+use tui::layout::Rect;
+use tui::style::{Color, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, Clear, Paragraph, Wrap};
+use tui::Frame;
+
+const HELP_TEXT: &str = "<Enter> or <Esc> to close";
+
+/// Popup for a failure the user can recover from, it is closed with <Enter> or <Esc>
+pub struct ErrorPopup {
+    message: String,
+}
+
+impl ErrorPopup {
+    pub fn new(message: String) -> Self {
+        ErrorPopup { message }
+    }
+
+    pub fn draw<B: Backend>(&self, f: &mut Frame<B>, rec: Rect) {
+        let popup = centered_rect(50, 30, rec);
+        let text = vec![
+            Spans::from(self.message.as_str()),
+            Spans::from(""),
+            Spans::from(Span::styled(HELP_TEXT, Style::default().fg(Color::Gray))),
+        ];
+        let error = Paragraph::new(text).wrap(Wrap { trim: true }).block(
+            Block::default()
+                .title("Error")
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Red)),
+        );
+        f.render_widget(Clear, popup);
+        f.render_widget(error, popup);
+    }
+}
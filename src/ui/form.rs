@@ -0,0 +1,241 @@
+use crate::model::secret::Secret;
+use crate::ui::centered_rect;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use tui::backend::Backend;
+use tui::layout::{Constraint, Layout, Rect};
+use tui::style::{Color, Style};
+use tui::text::Text;
+use tui::widgets::{Block, Borders, Clear, Paragraph, Wrap};
+use tui::Frame;
+use unicode_width::UnicodeWidthStr;
+
+const HELP_TEXT: &str = "<Tab> next field, <Enter> confirm, <Esc> cancel";
+const MULTILINE_HEIGHT: u16 = 6;
+
+struct FormField {
+    label: String,
+    value: Secret<String>,
+    masked: bool,
+    multiline: bool,
+}
+
+/// Popup with labelled text inputs, used for editing and creating safe content
+pub struct Form {
+    title: String,
+    fields: Vec<FormField>,
+    focus: usize,
+    reveal: bool,
+    can_reveal: bool,
+    // bound to the reveal action of the view, shown in the help of forms with secret fields
+    reveal_key: Option<String>,
+    error: Option<String>,
+    // keys of the view that work inside the form, shown after the help
+    hint: Option<String>,
+    submitted: bool,
+    cancelled: bool,
+}
+
+impl Form {
+    pub fn new(title: &str) -> Self {
+        Form {
+            title: title.to_string(),
+            fields: Vec::new(),
+            focus: 0,
+            reveal: false,
+            can_reveal: true,
+            reveal_key: None,
+            error: None,
+            hint: None,
+            submitted: false,
+            cancelled: false,
+        }
+    }
+
+    pub fn field(self, label: &str, value: &str) -> Self {
+        self.with_field(label, Secret::text(value.to_string()), false, false)
+    }
+
+    pub fn secret(self, label: &str, value: &str) -> Self {
+        self.with_field(label, Secret::text(value.to_string()), true, false)
+    }
+
+    /// Multi-line input, <Alt-Enter> inserts a line break
+    pub fn multiline(self, label: &str, value: Secret<String>) -> Self {
+        self.with_field(label, value, false, true)
+    }
+
+    fn with_field(
+        mut self,
+        label: &str,
+        value: Secret<String>,
+        masked: bool,
+        multiline: bool,
+    ) -> Self {
+        self.fields.push(FormField {
+            label: label.to_string(),
+            value,
+            masked,
+            multiline,
+        });
+        self
+    }
+
+    /// Whether the reveal key may show the secret fields
+    pub fn reveal(mut self, allowed: bool) -> Self {
+        self.can_reveal = allowed;
+        self
+    }
+
+    /// Key of the view that shows the secret fields, e.g. `<Ctrl-r>`
+    pub fn reveal_key(mut self, key: Option<String>) -> Self {
+        self.reveal_key = key;
+        self
+    }
+
+    pub fn toggle_reveal(&mut self) {
+        self.reveal = !self.reveal && self.can_reveal;
+    }
+
+    /// Added to the help of the form, e.g. `<Ctrl-t> generate a password`
+    pub fn hint(mut self, hint: Option<String>) -> Self {
+        self.hint = hint;
+        self
+    }
+
+    pub fn value(&self, index: usize) -> &str {
+        self.fields[index].value.expose()
+    }
+
+    pub fn set_value(&mut self, index: usize, value: &str) {
+        self.fields[index].value = Secret::text(value.to_string());
+    }
+
+    pub fn is_submitted(&self) -> bool {
+        self.submitted
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+
+    /// Reject the submitted values and keep the form open
+    pub fn set_error(&mut self, message: &str) {
+        self.error = Some(message.to_string());
+        self.submitted = false;
+    }
+
+    pub fn capture_key(&mut self, key: KeyEvent) {
+        let field = &mut self.fields[self.focus];
+        match (key.code, key.modifiers) {
+            (KeyCode::Esc, _) => self.cancelled = true,
+            (KeyCode::Enter, KeyModifiers::ALT) if field.multiline => field.value.push('\n'),
+            (KeyCode::Enter, _) => self.submitted = true,
+            (KeyCode::Tab | KeyCode::Down, _) => self.focus = (self.focus + 1) % self.fields.len(),
+            (KeyCode::BackTab | KeyCode::Up, _) => {
+                self.focus = (self.focus + self.fields.len() - 1) % self.fields.len()
+            }
+            (KeyCode::Backspace, _) => {
+                field.value.pop();
+            }
+            (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => field.value.push(c),
+            _ => {}
+        }
+    }
+
+    pub fn draw<B: Backend>(&self, f: &mut Frame<B>, rec: Rect) {
+        let mut constraints: Vec<Constraint> = self
+            .fields
+            .iter()
+            .map(|it| Constraint::Length(if it.multiline { MULTILINE_HEIGHT } else { 3 }))
+            .collect();
+        // two lines, the help wraps once a hint is added
+        constraints.push(Constraint::Length(4));
+        let height: u16 = self
+            .fields
+            .iter()
+            .map(|it| if it.multiline { MULTILINE_HEIGHT } else { 3 })
+            .sum::<u16>()
+            + 6;
+
+        let popup = centered_rect(60, 100, rec);
+        let popup = Rect {
+            y: popup.y + popup.height.saturating_sub(height) / 2,
+            height: height.min(popup.height),
+            ..popup
+        };
+        f.render_widget(Clear, popup);
+        f.render_widget(
+            Block::default()
+                .title(self.title.as_str())
+                .borders(Borders::ALL),
+            popup,
+        );
+        let chunks = Layout::default()
+            .margin(1)
+            .constraints(constraints)
+            .split(popup);
+
+        for (i, field) in self.fields.iter().enumerate() {
+            let display = if field.masked && !self.reveal {
+                Text::from(
+                    field
+                        .value
+                        .expose()
+                        .chars()
+                        .map(|_| '*')
+                        .collect::<String>(),
+                )
+            } else {
+                Text::from(field.value.expose().as_str())
+            };
+            let style = if i == self.focus {
+                Style::default().fg(Color::LightGreen)
+            } else {
+                Style::default()
+            };
+            let input = Paragraph::new(display).block(
+                Block::default()
+                    .title(field.label.as_str())
+                    .borders(Borders::ALL)
+                    .border_style(style),
+            );
+            f.render_widget(input, chunks[i]);
+        }
+        let help = match &self.error {
+            Some(e) => Paragraph::new(e.as_str()).style(Style::default().fg(Color::Red)),
+            None => Paragraph::new(self.help()),
+        }
+        .wrap(Wrap { trim: true })
+        .block(Block::default().borders(Borders::ALL));
+        f.render_widget(help, chunks[self.fields.len()]);
+
+        let focused = &self.fields[self.focus];
+        let last_line = focused
+            .value
+            .expose()
+            .rsplit('\n')
+            .next()
+            .unwrap_or_default();
+        let line = focused.value.expose().matches('\n').count() as u16;
+        f.set_cursor(
+            chunks[self.focus].x + last_line.width() as u16 + 1,
+            chunks[self.focus].y + 1 + line,
+        );
+    }
+
+    fn help(&self) -> String {
+        let mut help = HELP_TEXT.to_string();
+        let has_secret = self.fields.iter().any(|it| it.masked);
+        if let Some(key) = self
+            .reveal_key
+            .as_ref()
+            .filter(|_| has_secret && self.can_reveal)
+        {
+            help.push_str(&format!(", {} reveal", key));
+        }
+        if let Some(hint) = &self.hint {
+            help.push_str(&format!(", {}", hint));
+        }
+        help
+    }
+}
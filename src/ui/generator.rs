@@ -0,0 +1,392 @@
+use crate::model::diceware::{Diceware, Wordlist, MAX_WORDS};
+use crate::model::policy::{
+    PasswordPolicy, MAKE_PRONOUNCEABLE, MAX_LENGTH, USE_DIGITS, USE_EASY_VISION, USE_HEX_DIGITS,
+    USE_LOWERCASE, USE_SYMBOLS, USE_UPPERCASE,
+};
+use crate::model::secret::{Secret, MASK};
+use crate::ui::centered_rect;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::path::PathBuf;
+use tui::backend::Backend;
+use tui::layout::{Constraint, Layout, Rect};
+use tui::style::{Color, Modifier, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, Clear, Paragraph, Wrap};
+use tui::Frame;
+
+const HELP_TEXT: &str = "<Up>/<Down> setting, <Left>/<Right> or digits change it, <Space> on/off";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Row {
+    // a password or a passphrase of words
+    Kind,
+    Policy,
+    Length,
+    Class(u16),
+    SymbolSet,
+    // easy vision, pronounceable and hex digits exclude each other
+    Mode(u16),
+    Words,
+    Separator,
+    Capitalise,
+    Digits,
+    Wordlist,
+}
+
+const PASSWORD_ROWS: [(Row, &str); 11] = [
+    (Row::Kind, "kind"),
+    (Row::Policy, "policy"),
+    (Row::Length, "length"),
+    (Row::Class(USE_LOWERCASE), "lowercase"),
+    (Row::Class(USE_UPPERCASE), "uppercase"),
+    (Row::Class(USE_DIGITS), "digits"),
+    (Row::Class(USE_SYMBOLS), "symbols"),
+    (Row::SymbolSet, "symbol set"),
+    (Row::Mode(USE_EASY_VISION), "easy vision"),
+    (Row::Mode(MAKE_PRONOUNCEABLE), "pronounceable"),
+    (Row::Mode(USE_HEX_DIGITS), "hex digits only"),
+];
+
+const PASSPHRASE_ROWS: [(Row, &str); 6] = [
+    (Row::Kind, "kind"),
+    (Row::Words, "words"),
+    (Row::Separator, "separator"),
+    (Row::Capitalise, "capitalise"),
+    (Row::Digits, "digits"),
+    (Row::Wordlist, "wordlist"),
+];
+
+/// Popup that makes passwords by a password policy or passphrases of random words, changing a
+/// setting makes a new one
+pub struct Generator {
+    policy: PasswordPolicy,
+    diceware: Diceware,
+    passphrase: bool,
+    // the wordlist file is read once the first passphrase is made
+    wordlist_path: Option<PathBuf>,
+    wordlist: Option<Wordlist>,
+    // policies of the safe header, chosen in the first row after the custom one
+    named: Vec<(String, PasswordPolicy)>,
+    source: usize,
+    focus: usize,
+    password: Secret<String>,
+    reveal: bool,
+    // the generate key of the view, pressed again it makes another password
+    generate_key: Option<String>,
+    error: Option<String>,
+    submitted: bool,
+    cancelled: bool,
+}
+
+impl Generator {
+    /// Start with a named policy if it is one of the safe, otherwise with the given policy
+    pub fn new(
+        policy: PasswordPolicy,
+        name: Option<String>,
+        named: Vec<(String, PasswordPolicy)>,
+        wordlist_path: Option<PathBuf>,
+        reveal: bool,
+    ) -> Self {
+        let source = name
+            .and_then(|name| named.iter().position(|(n, _)| *n == name))
+            .map_or(0, |i| i + 1);
+        let mut generator = Generator {
+            policy,
+            diceware: Diceware::default(),
+            passphrase: false,
+            wordlist_path,
+            wordlist: None,
+            named,
+            source: 0,
+            focus: 0,
+            password: Secret::input(),
+            reveal,
+            generate_key: None,
+            error: None,
+            submitted: false,
+            cancelled: false,
+        };
+        generator.select_source(source);
+        generator
+    }
+
+    /// Key of the view that makes another password, e.g. `<Ctrl-t>`
+    pub fn generate_key(mut self, key: Option<String>) -> Self {
+        self.generate_key = key;
+        self
+    }
+
+    pub fn password(&self) -> &str {
+        self.password.expose()
+    }
+
+    pub fn is_submitted(&self) -> bool {
+        self.submitted
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled
+    }
+
+    pub fn capture_key(&mut self, key: KeyEvent) {
+        let rows = self.rows();
+        let (row, _) = rows[self.focus];
+        let typed = match key.code {
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => Some(c),
+            _ => None,
+        };
+        match (key.code, row) {
+            (KeyCode::Esc, _) => self.cancelled = true,
+            (KeyCode::Enter, _) => self.submitted = self.error.is_none(),
+            (KeyCode::Down | KeyCode::Tab, _) => self.focus = (self.focus + 1) % rows.len(),
+            (KeyCode::Up | KeyCode::BackTab, _) => {
+                self.focus = (self.focus + rows.len() - 1) % rows.len()
+            }
+            (KeyCode::Char(' ') | KeyCode::Left | KeyCode::Right, Row::Kind) => {
+                self.passphrase = !self.passphrase;
+                self.generate();
+            }
+            (KeyCode::Char(' ') | KeyCode::Left | KeyCode::Right, Row::Capitalise) => {
+                self.diceware.capitalise =
+                    self.diceware.capitalise.cycle(key.code != KeyCode::Left);
+                self.generate();
+            }
+            (KeyCode::Backspace, Row::Separator) => {
+                self.diceware.separator.pop();
+                self.generate();
+            }
+            (_, Row::Separator) => {
+                if let Some(c) = typed {
+                    self.diceware.separator.push(c);
+                    self.generate();
+                }
+            }
+            (KeyCode::Left | KeyCode::Right, Row::Policy) => {
+                let count = self.named.len() + 1;
+                let step = if key.code == KeyCode::Left {
+                    count - 1
+                } else {
+                    1
+                };
+                self.select_source((self.source + step) % count);
+            }
+            (KeyCode::Backspace, Row::SymbolSet) => {
+                let mut symbols = self.policy.symbols.take().unwrap_or_default();
+                symbols.pop();
+                self.policy.symbols = Some(symbols).filter(|s| !s.is_empty());
+                self.changed();
+            }
+            (_, Row::SymbolSet) => {
+                if let Some(c) = typed.filter(|c| !c.is_alphanumeric()) {
+                    let mut symbols = self.policy.symbols.take().unwrap_or_default();
+                    if !symbols.contains(c) {
+                        symbols.push(c);
+                    }
+                    self.policy.symbols = Some(symbols);
+                    self.changed();
+                }
+            }
+            (KeyCode::Char(' '), Row::Class(class)) => {
+                self.policy.toggle(class);
+                self.changed();
+            }
+            (KeyCode::Char(' ') | KeyCode::Left | KeyCode::Right, Row::Mode(mode)) => {
+                let on = !self.policy.has(mode);
+                self.policy.flags &= !(USE_EASY_VISION | MAKE_PRONOUNCEABLE | USE_HEX_DIGITS);
+                if on {
+                    self.policy.flags |= mode;
+                }
+                self.changed();
+            }
+            (_, Row::Length | Row::Class(_) | Row::Words | Row::Digits) => {
+                let (number, max) = match row {
+                    Row::Class(class) => (self.policy.min_count(class), MAX_LENGTH),
+                    Row::Words => (self.diceware.words, MAX_WORDS),
+                    Row::Digits => (self.diceware.digits, MAX_WORDS),
+                    _ => (self.policy.length, MAX_LENGTH),
+                };
+                let number = match (key.code, typed) {
+                    (KeyCode::Left, _) | (_, Some('-')) => number.saturating_sub(1),
+                    (KeyCode::Right, _) | (_, Some('+')) => number + 1,
+                    (KeyCode::Backspace, _) => number / 10,
+                    (_, Some(c)) if c.is_ascii_digit() => {
+                        number * 10 + c.to_digit(10).unwrap_or(0) as usize
+                    }
+                    _ => return,
+                }
+                .min(max);
+                match row {
+                    Row::Class(class) => self.policy.set_min_count(class, number),
+                    Row::Words => self.diceware.words = number,
+                    Row::Digits => self.diceware.digits = number,
+                    _ => self.policy.length = number,
+                }
+                self.changed();
+            }
+            _ => {}
+        }
+    }
+
+    pub fn draw<B: Backend>(&self, f: &mut Frame<B>, rec: Rect) {
+        let popup = centered_rect(60, 100, rec);
+        // the same size for both kinds, the popup doesn't jump when switching
+        let height = PASSWORD_ROWS.len() as u16 + 10;
+        let popup = Rect {
+            y: popup.y + popup.height.saturating_sub(height) / 2,
+            height: height.min(popup.height),
+            ..popup
+        };
+        f.render_widget(Clear, popup);
+        f.render_widget(
+            Block::default()
+                .title("Generate password")
+                .borders(Borders::ALL),
+            popup,
+        );
+        let chunks = Layout::default()
+            .margin(1)
+            .constraints([
+                Constraint::Length(PASSWORD_ROWS.len() as u16),
+                Constraint::Length(1),
+                Constraint::Length(4),
+                Constraint::Min(3),
+            ])
+            .split(popup);
+
+        let rows: Vec<Spans> = self
+            .rows()
+            .iter()
+            .enumerate()
+            .map(|(i, (row, label))| {
+                let style = if i == self.focus {
+                    Style::default()
+                        .fg(Color::LightGreen)
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default()
+                };
+                Spans::from(vec![
+                    Span::styled(format!("{:16}", label), style),
+                    Span::styled(self.value(*row), style),
+                ])
+            })
+            .collect();
+        f.render_widget(Paragraph::new(rows), chunks[0]);
+
+        let (text, style) = match &self.error {
+            Some(e) => (e.clone(), Style::default().fg(Color::Red)),
+            None => (
+                match self.reveal {
+                    true => self.password.expose().clone(),
+                    false => MASK.to_string(),
+                },
+                Style::default().fg(Color::Yellow),
+            ),
+        };
+        let entropy = match self.error {
+            Some(_) => String::new(),
+            None => format!("about {:.0} bits of entropy", self.entropy()),
+        };
+        let password = Paragraph::new(Span::styled(text, style))
+            .wrap(Wrap { trim: false })
+            .block(Block::default().title(entropy).borders(Borders::ALL));
+        f.render_widget(password, chunks[2]);
+
+        let another = match &self.generate_key {
+            Some(key) => format!(", {} another password", key),
+            None => String::new(),
+        };
+        let help = format!("{}{}, <Enter> use it, <Esc> cancel", HELP_TEXT, another);
+        let help = Paragraph::new(help)
+            .wrap(Wrap { trim: true })
+            .style(Style::default().fg(Color::Gray));
+        f.render_widget(help, chunks[3]);
+    }
+
+    fn rows(&self) -> &'static [(Row, &'static str)] {
+        if self.passphrase {
+            &PASSPHRASE_ROWS
+        } else {
+            &PASSWORD_ROWS
+        }
+    }
+
+    fn value(&self, row: Row) -> String {
+        let check = |on: bool| if on { "[x]" } else { "[ ]" };
+        match row {
+            Row::Kind if self.passphrase => "< passphrase >".to_string(),
+            Row::Kind => "< password >".to_string(),
+            Row::Policy => match self.source {
+                0 => "< custom >".to_string(),
+                i => format!("< {} >", self.named[i - 1].0),
+            },
+            Row::Length => self.policy.length.to_string(),
+            Row::Class(class) if self.policy.has(class) => {
+                format!("{} at least {}", check(true), self.policy.min_count(class))
+            }
+            Row::Class(_) => check(false).to_string(),
+            Row::SymbolSet => match &self.policy.symbols {
+                Some(symbols) => symbols.clone(),
+                None => format!("default {}", self.policy.symbol_set()),
+            },
+            Row::Mode(mode) => check(self.policy.has(mode)).to_string(),
+            Row::Words => self.diceware.words.to_string(),
+            Row::Separator if self.diceware.separator.is_empty() => "none".to_string(),
+            Row::Separator => format!("\"{}\"", self.diceware.separator),
+            Row::Capitalise => format!("< {} >", self.diceware.capitalise.name()),
+            Row::Digits => self.diceware.digits.to_string(),
+            Row::Wordlist => match &self.wordlist {
+                Some(wordlist) => wordlist.describe(),
+                None => "not read".to_string(),
+            },
+        }
+    }
+
+    fn select_source(&mut self, source: usize) {
+        self.source = source;
+        if let Some((_, policy)) = source.checked_sub(1).and_then(|i| self.named.get(i)) {
+            self.policy = policy.clone();
+        }
+        self.generate();
+    }
+
+    /// A changed setting makes a custom policy of a named one
+    fn changed(&mut self) {
+        self.source = 0;
+        self.generate();
+    }
+
+    pub fn generate(&mut self) {
+        if self.passphrase && self.wordlist.is_none() {
+            match Wordlist::load(self.wordlist_path.as_deref()) {
+                Ok(wordlist) => self.wordlist = Some(wordlist),
+                Err(e) => {
+                    self.password.wipe();
+                    self.error = Some(e.to_string());
+                    return;
+                }
+            }
+        }
+        let generated = match &self.wordlist {
+            Some(wordlist) if self.passphrase => self.diceware.generate(wordlist),
+            _ => self.policy.generate(),
+        };
+        match generated {
+            Ok(password) => {
+                self.password = Secret::text(password);
+                self.error = None;
+            }
+            Err(e) => {
+                self.password.wipe();
+                self.error = Some(e.to_string());
+            }
+        }
+    }
+
+    fn entropy(&self) -> f64 {
+        match &self.wordlist {
+            Some(wordlist) if self.passphrase => self.diceware.entropy(wordlist),
+            _ => self.policy.entropy(),
+        }
+    }
+}
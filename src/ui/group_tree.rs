@@ -0,0 +1,378 @@
+use crate::SafeModel;
+use std::collections::{BTreeMap, HashSet};
+use tui::style::{Color, Style};
+use tui::widgets::{ListItem, ListState};
+
+/// Path of the virtual group of expired and expiring entries, a group name can't hold it
+pub const EXPIRING: &str = "\u{0}expiring";
+/// Path of the node of the entries without a group
+pub const UNGROUPED: &str = "";
+
+struct GroupNode {
+    path: String,
+    name: String,
+    depth: usize,
+    // entries of the group and all of its subgroups
+    count: usize,
+    has_children: bool,
+}
+
+/// Collapsible tree of the dot separated group paths, only the visible rows are listed
+pub struct GroupTree {
+    nodes: Vec<GroupNode>,
+    expanded: HashSet<String>,
+    rows: Vec<usize>,
+    // days ahead of the virtual group
+    expiring_days: u64,
+    pub state: ListState,
+}
+
+impl GroupTree {
+    pub fn new(model: &SafeModel, expiring_days: u64) -> Self {
+        let mut tree = GroupTree {
+            nodes: Vec::new(),
+            expanded: HashSet::new(),
+            rows: Vec::new(),
+            expiring_days,
+            state: ListState::default(),
+        };
+        tree.reload(model);
+        tree
+    }
+
+    /// Rebuild from the model, expanded nodes and the selection are kept
+    pub fn reload(&mut self, model: &SafeModel) {
+        let selected = self.selected().map(str::to_string);
+        // ordered by segments, so every parent comes right before its children
+        let mut paths: BTreeMap<Vec<String>, (String, usize)> = BTreeMap::new();
+        for group in model.groups().into_iter().filter(|g| g != UNGROUPED) {
+            let count = model.by_group_name(&group).len();
+            let segments = split_path(&group);
+            for (depth, end) in separators(&group).into_iter().enumerate() {
+                let node = paths
+                    .entry(segments[..=depth].to_vec())
+                    .or_insert_with(|| (group[..end].to_string(), 0));
+                node.1 += count;
+            }
+        }
+        self.nodes = paths
+            .iter()
+            .map(|(segments, (path, count))| GroupNode {
+                path: path.clone(),
+                name: segments.last().cloned().unwrap_or_default(),
+                depth: segments.len() - 1,
+                count: *count,
+                has_children: paths
+                    .range(segments.clone()..)
+                    .nth(1)
+                    .is_some_and(|(next, _)| next.starts_with(segments)),
+            })
+            .collect();
+        // entries at the root of the safe, below the groups like pwsafe shows them
+        let ungrouped = model.by_group_name(UNGROUPED).len();
+        if ungrouped > 0 {
+            self.nodes.push(GroupNode {
+                path: UNGROUPED.to_string(),
+                name: "(no group)".to_string(),
+                depth: 0,
+                count: ungrouped,
+                has_children: false,
+            });
+        }
+        // on top while there is something to renew
+        let expiring = model.expiring(self.expiring_days).len();
+        if expiring > 0 {
+            self.nodes.insert(
+                0,
+                GroupNode {
+                    path: EXPIRING.to_string(),
+                    name: format!("Expired / expiring within {} days", self.expiring_days),
+                    depth: 0,
+                    count: expiring,
+                    has_children: false,
+                },
+            );
+        }
+        self.expanded
+            .retain(|p| paths.values().any(|(path, _)| path == p));
+        self.update_rows();
+        match selected {
+            Some(path) => self.select(&path),
+            None => self.state.select(None),
+        }
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.nodes.iter().any(|n| n.path == path)
+    }
+
+    /// The first real group, the virtual one is only opened on purpose
+    pub fn first(&self) -> Option<&str> {
+        self.rows
+            .iter()
+            .map(|&i| self.nodes[i].path.as_str())
+            .find(|&path| path != EXPIRING)
+    }
+
+    pub fn selected(&self) -> Option<&str> {
+        let row = self.state.selected()?;
+        self.rows.get(row).map(|&i| self.nodes[i].path.as_str())
+    }
+
+    /// Select a group, its parents are expanded to show it
+    pub fn select(&mut self, path: &str) {
+        for end in separators(path) {
+            if end < path.len() {
+                self.expanded.insert(path[..end].to_string());
+            }
+        }
+        self.update_rows();
+        let row = self.rows.iter().position(|&i| self.nodes[i].path == path);
+        self.state.select(row);
+    }
+
+    /// Number of visible rows
+    pub fn row_count(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn select_row(&mut self, row: usize) {
+        if !self.rows.is_empty() {
+            self.state.select(Some(row.min(self.rows.len() - 1)));
+        }
+    }
+
+    pub fn expand(&mut self) {
+        if let Some(node) = self.selected_node().filter(|n| n.has_children) {
+            self.expanded.insert(node.path.clone());
+            self.update_rows();
+        }
+    }
+
+    /// Collapse the selected group, or go to its parent if it is collapsed already
+    pub fn collapse(&mut self) {
+        let Some(node) = self.selected_node() else {
+            return;
+        };
+        let path = node.path.clone();
+        if self.expanded.remove(&path) {
+            self.update_rows();
+            self.select(&path);
+        } else if let Some(&end) = separators(&path).iter().rev().nth(1) {
+            self.select(&path[..end]);
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        match self.selected_node() {
+            Some(node) if self.expanded.contains(&node.path) => self.collapse(),
+            _ => self.expand(),
+        }
+    }
+
+    pub fn items(&self) -> Vec<ListItem<'static>> {
+        self.rows
+            .iter()
+            .map(|&i| {
+                let node = &self.nodes[i];
+                let marker = match (node.has_children, self.expanded.contains(&node.path)) {
+                    (false, _) => ' ',
+                    (true, true) => '▾',
+                    (true, false) => '▸',
+                };
+                let indent = "  ".repeat(node.depth);
+                let color = if node.path == EXPIRING {
+                    Color::LightRed
+                } else {
+                    Color::White
+                };
+                ListItem::new(format!(
+                    "{}{} {} ({})",
+                    indent, marker, node.name, node.count
+                ))
+                .style(Style::default().fg(color).bg(Color::Black))
+            })
+            .collect()
+    }
+
+    fn selected_node(&self) -> Option<&GroupNode> {
+        let row = self.state.selected()?;
+        self.rows.get(row).map(|&i| &self.nodes[i])
+    }
+
+    fn update_rows(&mut self) {
+        let selected = self.selected().map(str::to_string);
+        let mut rows = Vec::new();
+        // depth up to which the nodes are visible
+        let mut open_depth = 0;
+        for (i, node) in self.nodes.iter().enumerate() {
+            if node.depth > open_depth {
+                continue;
+            }
+            rows.push(i);
+            open_depth = if self.expanded.contains(&node.path) {
+                node.depth + 1
+            } else {
+                node.depth
+            };
+        }
+        self.rows = rows;
+        let row = selected.and_then(|p| self.rows.iter().position(|&i| self.nodes[i].path == p));
+        self.state.select(row);
+    }
+}
+
+/// Byte offsets where each level of the path ends, a dot escaped as `\.` is part of the name
+fn separators(path: &str) -> Vec<usize> {
+    let mut ends = Vec::new();
+    let mut escaped = false;
+    for (i, c) in path.char_indices() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            '.' if !escaped => ends.push(i),
+            _ => escaped = false,
+        }
+    }
+    ends.push(path.len());
+    ends
+}
+
+fn split_path(path: &str) -> Vec<String> {
+    let mut start = 0;
+    separators(path)
+        .into_iter()
+        .map(|end| {
+            let name = path[start..end].replace("\\.", ".");
+            start = end + 1;
+            name
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::record::{DbRecord, RecordField};
+    use std::{env, process};
+
+    /// A model that is never saved with one entry per group, "" for an entry without a group
+    fn model(groups: &[&str]) -> SafeModel {
+        let path = env::temp_dir().join(format!("tui-pwsafe-{}-group-tree", process::id()));
+        let mut model = SafeModel::create(&path.to_string_lossy())
+            .ok()
+            .expect("a path that does not exist");
+        for (i, group) in groups.iter().enumerate() {
+            let mut record = DbRecord::default();
+            record.set(RecordField::Title(format!("entry {}", i)));
+            if !group.is_empty() {
+                record.set(RecordField::Group(group.to_string()));
+            }
+            model.add_record(record).ok().expect("a titled record");
+        }
+        model
+    }
+
+    /// The visible rows, indented by depth
+    fn rows(tree: &GroupTree) -> Vec<String> {
+        tree.rows
+            .iter()
+            .map(|&i| {
+                let node = &tree.nodes[i];
+                format!("{}{} ({})", "  ".repeat(node.depth), node.name, node.count)
+            })
+            .collect()
+    }
+
+    fn paths(tree: &GroupTree) -> Vec<&str> {
+        tree.nodes.iter().map(|n| n.path.as_str()).collect()
+    }
+
+    #[test]
+    fn dot_paths_and_counts() {
+        let model = model(&[
+            "Work",
+            "Work.Mail",
+            "Work.Mail",
+            "Private",
+            "Work.Old\\.Mail",
+        ]);
+        let mut tree = GroupTree::new(&model, 30);
+        assert_eq!(
+            paths(&tree),
+            ["Private", "Work", "Work.Mail", "Work.Old\\.Mail"]
+        );
+        assert_eq!(rows(&tree), ["Private (1)", "Work (4)"]);
+        tree.select("Work.Mail");
+        assert_eq!(
+            rows(&tree),
+            ["Private (1)", "Work (4)", "  Mail (2)", "  Old.Mail (1)"]
+        );
+    }
+
+    #[test]
+    fn parents_without_entries() {
+        let mut model = model(&["A.B.C"]);
+        model.add_group("Empty");
+        let mut tree = GroupTree::new(&model, 30);
+        assert_eq!(paths(&tree), ["A", "A.B", "A.B.C", "Empty"]);
+        tree.select("A.B.C");
+        assert_eq!(rows(&tree), ["A (1)", "  B (1)", "    C (1)", "Empty (0)"]);
+        assert_eq!(tree.selected(), Some("A.B.C"));
+    }
+
+    #[test]
+    fn entries_without_a_group() {
+        let tree = GroupTree::new(&model(&["", "Work", ""]), 30);
+        assert_eq!(rows(&tree), ["Work (1)", "(no group) (2)"]);
+        assert!(tree.contains(UNGROUPED));
+        assert_eq!(tree.first(), Some("Work"));
+        let tree = GroupTree::new(&model(&["Work"]), 30);
+        assert!(!tree.contains(UNGROUPED));
+    }
+
+    #[test]
+    fn expand_and_collapse() {
+        let model = model(&["Private", "Work.Mail.Inbox"]);
+        let mut tree = GroupTree::new(&model, 30);
+        tree.select("Work");
+        tree.expand();
+        assert_eq!(rows(&tree), ["Private (1)", "Work (1)", "  Mail (1)"]);
+        assert_eq!(tree.selected(), Some("Work"));
+        // a group without subgroups has nothing to expand
+        tree.select("Private");
+        tree.expand();
+        assert_eq!(tree.row_count(), 3);
+        tree.select("Work.Mail.Inbox");
+        assert_eq!(tree.row_count(), 4);
+        // collapsed already, so the parent is selected
+        tree.collapse();
+        assert_eq!(tree.selected(), Some("Work.Mail"));
+        tree.collapse();
+        assert_eq!(tree.selected(), Some("Work.Mail"));
+        assert_eq!(rows(&tree), ["Private (1)", "Work (1)", "  Mail (1)"]);
+        tree.collapse();
+        assert_eq!(tree.selected(), Some("Work"));
+        tree.toggle();
+        assert_eq!(rows(&tree), ["Private (1)", "Work (1)"]);
+        tree.toggle();
+        assert_eq!(tree.row_count(), 3);
+        // a group at the top has no parent to go to
+        tree.select("Private");
+        tree.collapse();
+        assert_eq!(tree.selected(), Some("Private"));
+    }
+
+    #[test]
+    fn reload_keeps_the_selection_and_expanded_groups() {
+        let mut model = model(&["Work.Mail"]);
+        let mut tree = GroupTree::new(&model, 30);
+        tree.select("Work.Mail");
+        let mut record = DbRecord::default();
+        record.set(RecordField::Title("new".to_string()));
+        record.set(RecordField::Group("Work.Chat".to_string()));
+        model.add_record(record).ok().expect("a titled record");
+        tree.reload(&model);
+        assert_eq!(rows(&tree), ["Work (2)", "  Chat (1)", "  Mail (1)"]);
+        assert_eq!(tree.selected(), Some("Work.Mail"));
+    }
+}
@@ -1,27 +1,125 @@
+use crate::config::Config;
 use crate::contracts::UiWidgetVm;
+use crate::keymap::{Action, KeyMap};
+use crate::model::policy::PasswordPolicy;
+use crate::model::pwsfile::{MAX_ITERATIONS, MIN_ITERATIONS};
+use crate::model::record::{DbRecord, RecordField};
+use crate::model::search::SearchHit;
+use crate::model::secret::Secret;
+use crate::model::totp::{OtpAuth, TotpCode};
+use crate::model::{format_date, now};
+use crate::ui::entry_view::entry_lines;
+use crate::ui::error_popup::ErrorPopup;
+use crate::ui::form::Form;
+use crate::ui::generator::Generator;
+use crate::ui::group_tree::{GroupTree, EXPIRING, UNGROUPED};
 use crate::SafeModel;
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
-use rs_pwsafe::pwsdb::record::DbRecord;
-use std::collections::HashSet;
+use std::borrow::Cow;
+use std::time::{Duration, Instant};
 use tui::backend::Backend;
 use tui::layout::{Constraint, Direction, Layout, Rect};
 use tui::style::{Color, Modifier, Style};
 use tui::text::{Span, Spans};
-use tui::widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph};
+use tui::widgets::{Block, Borders, Clear, Gauge, List, ListItem, ListState, Paragraph, Wrap};
 use tui::Frame;
+use uuid::Uuid;
 
-const HELP_TEXT: &str = "Press <Ctrl-p> to copy password, <Ctrl-u> for username";
+// the help lists the keys currently bound to these actions
+const HELP: [(Action, &str); 20] = [
+    (Action::CopyPassword, "to copy password"),
+    (Action::CopyUsername, "for username"),
+    (Action::CopyTotp, "for the one-time code"),
+    (Action::SetTotp, "set it up"),
+    (Action::Reveal, "to reveal it"),
+    (Action::Edit, "to edit"),
+    (Action::Generate, "generate a password"),
+    (Action::NewEntry, "new entry"),
+    (Action::NewGroup, "new group"),
+    (Action::Delete, "delete"),
+    (Action::Move, "move"),
+    (Action::Undo, "undo"),
+    (Action::Redo, "redo"),
+    (Action::Save, "to save"),
+    (Action::ChangeMasterPassword, "change master password"),
+    (Action::ScrollUp, "scroll the entry up"),
+    (Action::ScrollDown, "and down"),
+    (Action::Search, "search"),
+    (Action::Lock, "lock the safe"),
+    (Action::CommandPalette, "all commands"),
+];
+
+const UNTITLED: &str = "(untitled)";
+// index of the password in the forms of an entry
+const PASSWORD_FIELD: usize = 2;
 
 pub struct ContentList {
-    groups: StatefulList<String>,
-    entries: StatefulList<String>,
+    groups: GroupTree,
+    entries: StatefulList<EntryItem>,
     active_group_name: String,
-    active_entry_name: Option<String>,
-    active_entry: Option<DbRecord>,
+    active_entry_id: Option<Uuid>,
+    active_entry: Option<Secret<DbRecord>>,
+    detail_scroll: u16,
+    detail_uuid: Option<Uuid>,
+    revealed_until: Option<Instant>,
+    // one-time code of the entry, renewed every tick
+    totp: Option<TotpCode>,
+    clipboard_left: Option<Duration>,
+    config: Config,
     select_group: bool,
-    selection: Option<String>,
+    selection: Option<Secret<String>>,
     help_text: String,
+    // the search has the focus and takes the typed characters
+    searching: bool,
     search_text: String,
+    // rows of the lists for a page move, known after the first draw
+    page_rows: usize,
+    search_changed: bool,
+    results: StatefulList<SearchHit>,
+    jump_to: Option<Uuid>,
+    form: Option<(FormPurpose, Form)>,
+    // on top of the form, or alone for a password to copy
+    generator: Option<Generator>,
+    named_policies: Vec<(String, PasswordPolicy)>,
+    error: Option<ErrorPopup>,
+    pending_change: Option<Change>,
+    save_requested: bool,
+    modified: bool,
+    iterations: u32,
+}
+
+enum FormPurpose {
+    Edit(Secret<DbRecord>),
+    NewEntry,
+    NewGroup,
+    Move(Uuid),
+    SetTotp(Secret<DbRecord>),
+    ChangeMasterPassword,
+}
+
+enum Change {
+    Update(DbRecord),
+    // the record with a new two-factor key, and the issuer and account of the key
+    SetTotp(DbRecord, String),
+    Add(DbRecord),
+    AddGroup(String),
+    Remove(Uuid),
+    Move(Uuid, String),
+    Undo,
+    Redo,
+    Rekey {
+        current: Secret<String>,
+        new: Secret<String>,
+        iterations: u32,
+    },
+}
+
+/// Row of the entries list, entries of subgroups show their group below the selected one
+struct EntryItem {
+    title: String,
+    subgroup: Option<String>,
+    uuid: Option<Uuid>,
+    expiry: Option<u32>,
 }
 
 struct StatefulList<T> {
@@ -29,16 +127,7 @@ struct StatefulList<T> {
     items: Vec<T>,
 }
 
-impl<T: Ord> StatefulList<T> {
-    fn with_hs(items: HashSet<T>) -> StatefulList<T> {
-        let mut vec: Vec<T> = Vec::from_iter(items);
-        vec.sort();
-        StatefulList {
-            state: ListState::default(),
-            items: vec,
-        }
-    }
-
+impl<T> StatefulList<T> {
     fn with_vec(items: Vec<T>) -> StatefulList<T> {
         StatefulList {
             state: ListState::default(),
@@ -46,114 +135,62 @@ impl<T: Ord> StatefulList<T> {
         }
     }
 
-    fn next(&mut self) {
-        let i = match self.state.selected() {
-            Some(i) => {
-                if i >= self.items.len() - 1 {
-                    0
-                } else {
-                    i + 1
-                }
-            }
-            None => 0,
-        };
-        self.state.select(Some(i));
-    }
-
-    fn previous(&mut self) {
-        let i = match self.state.selected() {
-            Some(i) => {
-                if i == 0 {
-                    self.items.len() - 1
-                } else {
-                    i - 1
-                }
-            }
-            None => 0,
-        };
-        self.state.select(Some(i));
+    fn select_row(&mut self, row: usize) {
+        if !self.items.is_empty() {
+            self.state.select(Some(row.min(self.items.len() - 1)));
+        }
     }
 }
 
 impl<B: Backend> UiWidgetVm<B> for ContentList {
     fn capture_key(&mut self, key: KeyEvent) {
-        match key {
-            KeyEvent {
-                kind: _,
-                state: _,
-                code: KeyCode::Char('p'),
-                modifiers: KeyModifiers::CONTROL,
-            } => {
-                if let Some(item) = &self.active_entry {
-                    self.selection = item.password();
-                    self.help_text = "Password copied".to_string();
-                }
+        if self.error.is_some() {
+            if matches!(key.code, KeyCode::Enter | KeyCode::Esc) {
+                self.error = None;
             }
-            KeyEvent {
-                kind: _,
-                state: _,
-                code: KeyCode::Char('u'),
-                modifiers: KeyModifiers::CONTROL,
-            } => {
-                if let Some(item) = &self.active_entry {
-                    self.selection = item.username();
-                    self.help_text = "Username copied".to_string();
-                }
+            return;
+        }
+        let action = self.config.keys.text_action(&key);
+        if let Some(generator) = &mut self.generator {
+            if action == Some(Action::Generate) {
+                generator.generate();
+                return;
             }
-            KeyEvent {
-                kind: _,
-                state: _,
-                code: _,
-                modifiers: KeyModifiers::NONE,
-            } => match key.code {
-                KeyCode::Tab => self.select_group = !self.select_group,
-                KeyCode::Down => {
-                    self.search_text = String::new();
-                    if self.select_group {
-                        self.groups.next();
-                        if let Some(selected) = self.groups.state.selected() {
-                            self.active_group_name = self.groups.items[selected].clone();
-                        }
-                    } else {
-                        self.entries.next();
-                        if let Some(selected) = self.entries.state.selected() {
-                            self.active_entry_name = Some(self.entries.items[selected].clone());
-                        }
-                    }
-                }
-                KeyCode::Up => {
-                    self.search_text = String::new();
-                    if self.select_group {
-                        self.groups.previous();
-                        if let Some(selected) = self.groups.state.selected() {
-                            self.active_group_name = self.groups.items[selected].clone();
-                        }
-                    } else {
-                        self.entries.previous();
-                        if let Some(selected) = self.entries.state.selected() {
-                            self.active_entry_name = Some(self.entries.items[selected].clone());
-                        }
-                    }
-                }
-                KeyCode::Backspace => {
-                    self.search_text.pop();
-                }
-                KeyCode::Char(char) => {
-                    self.search_text.push(char);
-                    if let Some(pos) = self
-                        .entries
-                        .items
-                        .iter()
-                        .position(|entry| entry.to_lowercase().starts_with(&self.search_text))
-                    {
-                        self.entries.state.select(Some(pos));
-                        self.active_entry_name = Some(self.entries.items[pos].clone());
-                    }
-                }
-                _ => {}
-            },
-            _ => {}
-        };
+            generator.capture_key(key);
+            if generator.is_cancelled() {
+                self.generator = None;
+            } else if generator.is_submitted() {
+                self.use_generated();
+            }
+            return;
+        }
+        let for_entry = matches!(
+            self.form,
+            Some((FormPurpose::Edit(_) | FormPurpose::NewEntry, _))
+        );
+        if for_entry && action == Some(Action::Generate) {
+            self.open_generator();
+            return;
+        }
+        if let Some((_, form)) = &mut self.form {
+            if action == Some(Action::Reveal) {
+                form.toggle_reveal();
+                return;
+            }
+            form.capture_key(key);
+            if form.is_cancelled() {
+                self.form = None;
+            } else if form.is_submitted() {
+                self.submit_form();
+            }
+            return;
+        }
+        if self.is_searching() && self.search_key(key) {
+            return;
+        }
+        if let Some(action) = self.config.keys.action(&key) {
+            self.run_action(action);
+        }
     }
 
     fn draw(&mut self, f: &mut Frame<B>, rec: Rect) {
@@ -168,14 +205,7 @@ impl<B: Backend> UiWidgetVm<B> for ContentList {
             .split(rec);
 
         // groups
-        let items: Vec<ListItem> = self
-            .groups
-            .items
-            .iter()
-            .map(|it| {
-                ListItem::new(it.as_str()).style(Style::default().fg(Color::White).bg(Color::Black))
-            })
-            .collect();
+        let items = self.groups.items();
 
         let left_block = Layout::default()
             .direction(Direction::Vertical)
@@ -192,28 +222,48 @@ impl<B: Backend> UiWidgetVm<B> for ContentList {
             .highlight_symbol(">> ");
         f.render_stateful_widget(list, left_block[0], &mut self.groups.state);
 
+        // the query stays after the search for the next and previous match
+        let search_style = if self.is_searching() {
+            Style::default().fg(Color::LightGreen)
+        } else {
+            Style::default().fg(Color::Gray)
+        };
         let search_block = Paragraph::new(self.search_text.clone()).block(
             Block::default()
                 .title(Span::styled(
                     "search:",
                     Style::default().fg(Color::White).bg(Color::Black),
                 ))
-                .borders(Borders::ALL),
+                .borders(Borders::ALL)
+                .border_style(search_style),
         );
         f.render_widget(search_block, left_block[1]);
 
-        // entries
-        let items: Vec<ListItem> = self
-            .entries
-            .items
-            .iter()
-            .map(|it| {
-                ListItem::new(it.as_str()).style(Style::default().fg(Color::White).bg(Color::Black))
-            })
-            .collect();
-
+        // entries, or the search results while searching
+        self.page_rows = chunks[1].height.saturating_sub(2) as usize;
+        let modified = if self.modified { " [modified]" } else { "" };
+        let (items, title, state) = if self.is_searching() {
+            let items: Vec<ListItem> = self.results.items.iter().map(result_item).collect();
+            let title = format!("Search results ({}){}", items.len(), modified);
+            (items, title, &mut self.results.state)
+        } else {
+            let now = now();
+            let soon =
+                u64::from(now).saturating_add(self.config.expiring_days.saturating_mul(86_400));
+            let items: Vec<ListItem> = self
+                .entries
+                .items
+                .iter()
+                .map(|e| entry_item(e, now, soon))
+                .collect();
+            (
+                items,
+                format!("Entries{}", modified),
+                &mut self.entries.state,
+            )
+        };
         let entire_list = List::new(items)
-            .block(Block::default().borders(Borders::ALL).title("Groups"))
+            .block(Block::default().borders(Borders::ALL).title(title))
             .highlight_style(
                 Style::default()
                     .fg(Color::LightGreen)
@@ -221,86 +271,922 @@ impl<B: Backend> UiWidgetVm<B> for ContentList {
             )
             .highlight_symbol(">> ");
 
-        f.render_stateful_widget(entire_list, chunks[1], &mut self.entries.state);
+        f.render_stateful_widget(entire_list, chunks[1], state);
 
         // entrie
-        if let Some(entry) = &self.active_entry {
+        if let Some(entry) = self.active_entry.as_ref().map(Secret::expose) {
             let sub_layout = Layout::default()
                 .direction(Direction::Vertical)
-                .constraints([Constraint::Percentage(80), Constraint::Percentage(20)])
+                .constraints([
+                    Constraint::Min(3),
+                    Constraint::Length(if self.totp.is_some() { 3 } else { 0 }),
+                    Constraint::Percentage(20),
+                ])
                 .split(chunks[2]);
 
-            let mut text = vec![Spans::from(format!(
-                "{:12}: {}",
-                "title",
-                entry.title().unwrap()
-            ))];
-            if let Some(uname) = entry.username() {
-                text.push(Spans::from(format!("{:12}: {}", "username", uname)))
-            }
-            if let Some(pw) = entry.password() {
-                text.push(Spans::from(format!("{:12}: {}", "password", pw)))
-            }
+            let reveal = self
+                .revealed_until
+                .map(|until| until.saturating_duration_since(Instant::now()));
+            let text = entry_lines(entry, reveal);
+            // keep the last line of the notes in view
+            self.detail_scroll = self.detail_scroll.min(text.len().saturating_sub(1) as u16);
             let entrie_p = Paragraph::new(text)
+                .wrap(Wrap { trim: false })
+                .scroll((self.detail_scroll, 0))
                 .style(Style::default().bg(Color::White).fg(Color::Black))
                 .block(Block::default().borders(Borders::ALL).title("Entry"));
             f.render_widget(entrie_p, sub_layout[0]);
 
-            let help = Paragraph::new(HELP_TEXT)
+            if let Some(totp) = &self.totp {
+                f.render_widget(totp_gauge(totp, self.config.copy_only), sub_layout[1]);
+            }
+
+            let mut help_text = vec![Spans::from(self.help_text.as_str())];
+            if let Some(left) = self.clipboard_left {
+                help_text.push(Spans::from(Span::styled(
+                    format!("Clipboard is cleared in {}s", left.as_secs() + 1),
+                    Style::default().fg(Color::Yellow),
+                )));
+            }
+            let help = Paragraph::new(help_text)
+                .wrap(Wrap { trim: true })
                 .style(Style::default().bg(Color::Black).fg(Color::Gray))
                 .block(Block::default().borders(Borders::ALL).title("Help"));
-            f.render_widget(help, sub_layout[1]);
+            f.render_widget(help, sub_layout[2]);
+        }
+
+        if let Some((_, form)) = &self.form {
+            form.draw(f, rec);
+        }
+        if let Some(generator) = &self.generator {
+            generator.draw(f, rec);
+        }
+        if let Some(error) = &self.error {
+            error.draw(f, rec);
         }
     }
 
     fn update_model(&mut self, model: &mut SafeModel) {
-        let items_in_group = model.by_group_name(self.active_group_name.as_str());
+        if self.search_changed {
+            self.search_changed = false;
+            self.results = StatefulList::with_vec(model.search(self.search_text.as_str()));
+            if !self.results.items.is_empty() {
+                self.results.state.select(Some(0));
+            }
+        }
+        if self.is_searching() {
+            // the selected hit is shown, loaded from the safe as hits copy no secrets
+            let hit = self
+                .results
+                .state
+                .selected()
+                .and_then(|i| self.results.items[i].uuid);
+            let active = self.active_entry.as_ref().and_then(|e| e.expose().uuid());
+            if hit.is_some() && hit != active {
+                self.active_entry = hit.and_then(|u| model.by_uuid(u)).map(Secret::copy_of);
+            }
+        }
+        if let Some(uuid) = self.jump_to.take() {
+            self.select_record(model, Some(uuid));
+        }
+        if self.pending_change.is_some() {
+            // results would show the records before the change
+            self.end_search();
+        }
+
         if self.select_group {
-            self.entries.items = items_in_group.iter().map(|&e| e.title().unwrap()).collect();
-            self.entries.items.sort();
+            self.reload_entries(model, None);
             self.entries.state.select(Some(0));
         }
-        if let Some(name) = &self.active_entry_name {
-            if let Some(&record) = items_in_group
-                .iter()
-                .find(|&&i| i.title().is_some() && i.title().unwrap().as_str().eq(name.as_str()))
-            {
-                self.active_entry = Some(record.clone());
+        if let Some(uuid) = self.active_entry_id.take() {
+            if let Some(record) = model.by_uuid(uuid) {
+                self.active_entry = Some(Secret::copy_of(record));
+            }
+        }
+
+        if let Some(content) = self.selection.take() {
+            if let Err(e) = model.set_clipboard(content.expose(), self.config.clipboard_timeout) {
+                self.show_error(format!("Copying failed: {}", e));
+            }
+        }
+        self.clipboard_left = model.clipboard_remaining();
+
+        match self.pending_change.take() {
+            Some(Change::Update(record)) => {
+                let uuid = record.uuid();
+                match model.update_record(record) {
+                    Ok(_) => {
+                        self.select_record(model, uuid);
+                        self.help_text = self.hint("Entry changed", Action::Save, "to save");
+                    }
+                    Err(e) => self.show_error(format!("Changing the entry failed: {}", e)),
+                }
+            }
+            Some(Change::SetTotp(record, name)) => {
+                let uuid = record.uuid();
+                match model.update_record(record) {
+                    Ok(_) => {
+                        self.select_record(model, uuid);
+                        // the first code to check against the service
+                        let code = uuid
+                            .and_then(|uuid| model.totp(uuid))
+                            .map(|totp| totp.code())
+                            .filter(|_| !self.config.copy_only);
+                        let mut message = match name.as_str() {
+                            "" => "One-time codes set up".to_string(),
+                            name => format!("One-time codes of {} set up", name),
+                        };
+                        if let Some(code) = code {
+                            message =
+                                format!("{}, the code now is {}", message, code.code.expose());
+                        }
+                        self.help_text = self.hint(&message, Action::Save, "to save");
+                    }
+                    Err(e) => self.show_error(format!("Setting up the codes failed: {}", e)),
+                }
+            }
+            Some(Change::Add(record)) => match model.add_record(record) {
+                Ok(uuid) => {
+                    self.reload_groups(model);
+                    self.select_record(model, Some(uuid));
+                    self.help_text = self.hint("Entry added", Action::Save, "to save");
+                }
+                Err(e) => self.show_error(format!("Adding the entry failed: {}", e)),
+            },
+            Some(Change::AddGroup(name)) => {
+                model.add_group(name.as_str());
+                self.reload_groups(model);
+                self.show_entry(model, Some(name), None);
+                self.help_text = self.hint("Group added", Action::Save, "to save");
+            }
+            Some(Change::Remove(uuid)) => {
+                model.remove_record(uuid);
+                self.active_entry = None;
+                self.reload_groups(model);
+                self.show_entry(model, Some(self.active_group_name.clone()), None);
+                self.help_text = self.hint("Entry deleted", Action::Undo, "to undo");
+            }
+            Some(Change::Move(uuid, group)) => match model.move_record(uuid, group.as_str()) {
+                Ok(_) => {
+                    self.reload_groups(model);
+                    self.select_record(model, Some(uuid));
+                    let message = format!("Entry moved to {}", group);
+                    self.help_text = self.hint(&message, Action::Undo, "to undo");
+                }
+                Err(e) => self.show_error(format!("Moving the entry failed: {}", e)),
+            },
+            Some(Change::Undo) => {
+                self.help_text = match model.undo() {
+                    Some(uuid) => {
+                        self.reload_groups(model);
+                        self.select_record(model, uuid);
+                        "Undone".to_string()
+                    }
+                    None => "Nothing to undo".to_string(),
+                };
+            }
+            Some(Change::Rekey {
+                current,
+                new,
+                iterations,
+            }) => match model.change_master_password(current.expose(), new.expose(), iterations) {
+                Ok(_) => {
+                    self.form = None;
+                    self.iterations = iterations;
+                    self.help_text = "Master password changed and safe saved".to_string();
+                }
+                Err(e) => {
+                    if let Some((_, form)) = &mut self.form {
+                        form.set_error(&format!("Changing the master password failed: {}", e));
+                    }
+                }
+            },
+            Some(Change::Redo) => {
+                self.help_text = match model.redo() {
+                    Some(uuid) => {
+                        self.reload_groups(model);
+                        self.select_record(model, uuid);
+                        "Redone".to_string()
+                    }
+                    None => "Nothing to redo".to_string(),
+                };
             }
-            self.active_entry_name = None;
+            None => {}
         }
 
-        if let Some(content) = &self.selection {
-            model.to_clipboard(content.as_str());
-            self.help_text.push('*');
-            self.selection = None;
+        if self.save_requested {
+            self.save_requested = false;
+            match model.save() {
+                Ok(_) => self.help_text = "Safe saved".to_string(),
+                Err(e) => self.show_error(format!("Saving failed: {}", e)),
+            }
         }
+        self.modified = model.is_dirty();
+        let uuid = self.active_entry.as_ref().and_then(|e| e.expose().uuid());
+        if uuid != self.detail_uuid {
+            self.detail_uuid = uuid;
+            self.detail_scroll = 0;
+            self.revealed_until = None;
+        }
+        if self
+            .revealed_until
+            .is_some_and(|until| until <= Instant::now())
+        {
+            self.revealed_until = None;
+        }
+        self.totp = uuid
+            .and_then(|uuid| model.totp(uuid))
+            .map(|totp| totp.code());
     }
 
     fn is_done(&self) -> bool {
-        return false;
+        false
+    }
+
+    fn has_popup(&self) -> bool {
+        self.form.is_some()
+            || self.generator.is_some()
+            || self.error.is_some()
+            || self.is_searching()
+    }
+
+    fn takes_text(&self) -> bool {
+        self.form.is_some() || self.generator.is_some() || self.is_searching()
+    }
+
+    fn location(&self) -> Option<(String, Option<Uuid>)> {
+        let entry = self.active_entry.as_ref().and_then(|e| e.expose().uuid());
+        Some((self.active_group_name.clone(), entry))
+    }
+
+    fn commands(&self) -> Vec<Action> {
+        Action::all()
+            .filter(|a| !matches!(a, Action::Quit | Action::Lock | Action::CommandPalette))
+            .collect()
+    }
+
+    fn run_command(&mut self, action: Action) {
+        self.run_action(action);
     }
 }
 
 impl ContentList {
-    pub fn new(model: &SafeModel) -> Self {
-        let groups = model.groups();
-        let item_list = StatefulList::with_hs(groups);
-        let entries: Vec<String> = model
-            .by_group_name(item_list.items.first().unwrap().as_str())
-            .iter()
-            .map(|&e| e.title().unwrap())
-            .collect();
-        ContentList {
+    pub fn new(model: &SafeModel, config: Config) -> Self {
+        let mut groups = GroupTree::new(model, config.expiring_days);
+        let first = groups.first().unwrap_or_default().to_string();
+        groups.select(&first);
+        let mut list = ContentList {
+            searching: false,
             search_text: String::new(),
-            help_text: HELP_TEXT.to_string(),
+            page_rows: 0,
+            search_changed: false,
+            results: StatefulList::with_vec(Vec::new()),
+            jump_to: None,
+            help_text: help_text(&config.keys),
             selection: None,
-            groups: item_list,
+            groups,
             select_group: true,
-            active_group_name: String::new(),
-            active_entry_name: None,
+            active_group_name: first,
+            active_entry_id: None,
             active_entry: None,
-            entries: StatefulList::with_vec(entries),
+            detail_scroll: 0,
+            detail_uuid: None,
+            revealed_until: None,
+            totp: None,
+            clipboard_left: None,
+            entries: StatefulList::with_vec(Vec::new()),
+            form: None,
+            generator: None,
+            named_policies: model.named_policies(),
+            error: None,
+            pending_change: None,
+            save_requested: false,
+            modified: false,
+            iterations: model.iterations(),
+            config,
+        };
+        list.reload_entries(model, None);
+        list
+    }
+
+    /// Go back to a location of [UiWidgetVm::location], e.g. after the safe was locked
+    pub fn restore(&mut self, model: &SafeModel, group: String, entry: Option<Uuid>) {
+        if self.groups.contains(&group) {
+            self.show_entry(model, Some(group), entry);
+            self.active_entry_id = entry;
         }
     }
+
+    fn show_error(&mut self, message: String) {
+        self.error = Some(ErrorPopup::new(message));
+    }
+
+    /// Text editing of the search, other keys keep their action while searching
+    fn search_key(&mut self, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Esc => self.end_search(),
+            KeyCode::Enter => {
+                if let Some(i) = self.results.state.selected() {
+                    self.jump_to = self.results.items[i].uuid;
+                }
+                // back to normal mode, the results stay for the next match
+                self.searching = false;
+                self.help_text = help_text(&self.config.keys);
+            }
+            KeyCode::Backspace => {
+                let mut text = self.search_text.clone();
+                text.pop();
+                self.set_search(text);
+            }
+            KeyCode::Char(c)
+                if !key
+                    .modifiers
+                    .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+            {
+                self.set_search(format!("{}{}", self.search_text, c));
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn run_action(&mut self, action: Action) {
+        match action {
+            // handled by the app unless a popup is open
+            Action::Quit | Action::Lock | Action::CommandPalette => {}
+            Action::NextPane => self.select_group = !self.select_group,
+            Action::Up
+            | Action::Down
+            | Action::Top
+            | Action::Bottom
+            | Action::PageUp
+            | Action::PageDown => self.move_selection(action),
+            Action::Expand if self.select_group => self.groups.expand(),
+            Action::Collapse if self.select_group => {
+                self.groups.collapse();
+                self.active_group_name = self.groups.selected().unwrap_or_default().into();
+            }
+            Action::Select if self.select_group => self.groups.toggle(),
+            Action::Expand | Action::Collapse | Action::Select => {}
+            Action::ScrollDown => self.detail_scroll = self.detail_scroll.saturating_add(5),
+            Action::ScrollUp => self.detail_scroll = self.detail_scroll.saturating_sub(5),
+            Action::Search => self.start_search(),
+            Action::NextMatch | Action::PreviousMatch => self.jump_to_match(action),
+            Action::CopyPassword => {
+                if let Some(item) = self.active_entry.as_ref().map(Secret::expose) {
+                    self.selection = item.password().map(|p| Secret::text(p.to_string()));
+                    self.help_text = "Password copied".to_string();
+                }
+            }
+            Action::CopyUsername => {
+                if let Some(item) = self.active_entry.as_ref().map(Secret::expose) {
+                    self.selection = item.username().map(|u| Secret::text(u.to_string()));
+                    self.help_text = "Username copied".to_string();
+                }
+            }
+            Action::CopyTotp => match &self.totp {
+                Some(totp) => {
+                    self.selection = Some(totp.code.duplicate());
+                    self.help_text = "One-time code copied".to_string();
+                }
+                None => self.help_text = "The entry has no two-factor key".to_string(),
+            },
+            Action::Reveal => {
+                if self.config.copy_only {
+                    self.help_text =
+                        self.hint("Passwords are never shown", Action::CopyPassword, "to copy");
+                } else if self.revealed_until.take().is_none() && self.active_entry.is_some() {
+                    self.revealed_until = Some(Instant::now() + self.config.reveal_timeout);
+                }
+            }
+            Action::Edit => {
+                if let Some(item) = self.active_entry.as_ref().map(Secret::expose) {
+                    let form = edit_form(item, !self.config.copy_only)
+                        .hint(generate_hint(&self.config.keys));
+                    self.open_form(FormPurpose::Edit(Secret::copy_of(item)), form);
+                }
+            }
+            Action::Generate => self.open_generator(),
+            Action::NewEntry => {
+                let form = new_entry_form(self.real_group()).hint(generate_hint(&self.config.keys));
+                self.open_form(FormPurpose::NewEntry, form);
+            }
+            Action::NewGroup => {
+                let form = Form::new("New group").field("name", "");
+                self.open_form(FormPurpose::NewGroup, form);
+            }
+            Action::Delete if !self.select_group || self.is_searching() => {
+                if let Some(uuid) = self.active_entry.as_ref().and_then(|e| e.expose().uuid()) {
+                    self.pending_change = Some(Change::Remove(uuid));
+                }
+            }
+            Action::Delete => {}
+            Action::Move => {
+                if let Some(uuid) = self.active_entry.as_ref().and_then(|e| e.expose().uuid()) {
+                    let form = Form::new("Move entry to group").field("group", self.real_group());
+                    self.open_form(FormPurpose::Move(uuid), form);
+                }
+            }
+            Action::SetTotp => {
+                if let Some(item) = self.active_entry.as_ref().map(Secret::expose) {
+                    let form = Form::new("Set up one-time codes")
+                        .secret("otpauth:// URI or file", "")
+                        .reveal(!self.config.copy_only);
+                    self.open_form(FormPurpose::SetTotp(Secret::copy_of(item)), form);
+                }
+            }
+            Action::ChangeMasterPassword => {
+                let form = Form::new("Change master password")
+                    .secret("current master password", "")
+                    .secret("new master password", "")
+                    .secret("confirm new master password", "")
+                    .field("key stretch iterations", &self.iterations.to_string());
+                self.open_form(FormPurpose::ChangeMasterPassword, form);
+            }
+            Action::Undo => self.pending_change = Some(Change::Undo),
+            Action::Redo => self.pending_change = Some(Change::Redo),
+            Action::Save => self.save_requested = true,
+        }
+    }
+
+    fn open_form(&mut self, purpose: FormPurpose, form: Form) {
+        let form = form.reveal_key(self.config.keys.text_key(Action::Reveal));
+        self.form = Some((purpose, form));
+    }
+
+    /// Generator for the password of the open form, or for the clipboard without one
+    fn open_generator(&mut self) {
+        let (policy, name) = match &self.form {
+            Some((FormPurpose::Edit(entry), _)) => entry_policy(entry.expose()),
+            _ => (PasswordPolicy::default(), None),
+        };
+        self.generator = Some(
+            Generator::new(
+                policy,
+                name,
+                self.named_policies.clone(),
+                self.config.wordlist.clone(),
+                !self.config.copy_only,
+            )
+            .generate_key(self.config.keys.text_key(Action::Generate)),
+        );
+    }
+
+    fn use_generated(&mut self) {
+        let Some(generator) = self.generator.take() else {
+            return;
+        };
+        match &mut self.form {
+            Some((_, form)) => form.set_value(PASSWORD_FIELD, generator.password()),
+            None => {
+                self.selection = Some(Secret::text(generator.password().to_string()));
+                self.help_text = "Generated password copied".to_string();
+            }
+        }
+    }
+
+    /// A message followed by the key of the next step, e.g. `Entry added, <Ctrl-s> to save`
+    fn hint(&self, message: &str, action: Action, text: &str) -> String {
+        match self.config.keys.key(action) {
+            Some(key) => format!("{}, {} {}", message, key, text),
+            None => message.to_string(),
+        }
+    }
+
+    /// Move in the focused list, up and down wrap around, a page is half of the visible rows
+    fn move_selection(&mut self, action: Action) {
+        let half_page = (self.page_rows / 2).max(1);
+        let row = |selected: Option<usize>, len: usize| match action {
+            Action::Up => selected.map_or(0, |r| (r + len - 1) % len),
+            Action::Down => selected.map_or(0, |r| (r + 1) % len),
+            Action::Top => 0,
+            Action::Bottom => len - 1,
+            Action::PageUp => selected.unwrap_or(0).saturating_sub(half_page),
+            _ => selected.map_or(0, |r| r + half_page),
+        };
+        if self.is_searching() {
+            if self.results.items.is_empty() {
+                return;
+            }
+            self.results
+                .select_row(row(self.results.state.selected(), self.results.items.len()));
+        } else if self.select_group {
+            if self.groups.row_count() == 0 {
+                return;
+            }
+            self.groups
+                .select_row(row(self.groups.state.selected(), self.groups.row_count()));
+            self.active_group_name = self.groups.selected().unwrap_or_default().into();
+        } else {
+            if self.entries.items.is_empty() {
+                return;
+            }
+            self.entries
+                .select_row(row(self.entries.state.selected(), self.entries.items.len()));
+            if let Some(selected) = self.entries.state.selected() {
+                self.active_entry_id = self.entries.items[selected].uuid;
+            }
+        }
+    }
+
+    /// Go to the next or previous result of the last search, like `n` and `N` in vim
+    fn jump_to_match(&mut self, action: Action) {
+        let len = self.results.items.len();
+        if len == 0 {
+            self.help_text = self.hint("No search results", Action::Search, "to search");
+            return;
+        }
+        let row = match (self.results.state.selected(), action) {
+            (None, _) => 0,
+            (Some(r), Action::NextMatch) => (r + 1) % len,
+            (Some(r), _) => (r + len - 1) % len,
+        };
+        self.results.state.select(Some(row));
+        self.searching = false;
+        self.jump_to = self.results.items[row].uuid;
+        self.help_text = format!("Match {} of {} for \"{}\"", row + 1, len, self.search_text);
+    }
+
+    fn is_searching(&self) -> bool {
+        self.searching
+    }
+
+    /// Search mode, typed characters go into a new query until <Enter> or <Esc>
+    fn start_search(&mut self) {
+        self.set_search(String::new());
+        self.searching = true;
+        self.help_text = search_help(&self.config.keys);
+    }
+
+    /// Leave the search, the results go with the query
+    fn end_search(&mut self) {
+        if self.searching {
+            self.searching = false;
+            self.help_text = help_text(&self.config.keys);
+        }
+        self.search_text.clear();
+        self.search_changed = false;
+        self.results = StatefulList::with_vec(Vec::new());
+    }
+
+    /// Change the query, the results are updated with the model
+    fn set_search(&mut self, text: String) {
+        if text.is_empty() {
+            self.results = StatefulList::with_vec(Vec::new());
+        }
+        self.search_changed = !text.is_empty();
+        self.search_text = text;
+    }
+
+    fn submit_form(&mut self) {
+        let Some((purpose, form)) = &mut self.form else {
+            return;
+        };
+        if let FormPurpose::Move(uuid) = purpose {
+            let group = form.value(0).trim();
+            if group.is_empty() {
+                form.set_error("A group is required");
+                return;
+            }
+            self.pending_change = Some(Change::Move(*uuid, group.to_string()));
+            self.form = None;
+            return;
+        }
+        if let FormPurpose::SetTotp(entry) = purpose {
+            match OtpAuth::read(form.value(0)) {
+                Ok(otp) => {
+                    let mut record = entry.take();
+                    otp.apply(&mut record);
+                    self.pending_change = Some(Change::SetTotp(record, otp.name()));
+                    self.form = None;
+                }
+                Err(e) => form.set_error(&e.to_string()),
+            }
+            return;
+        }
+        if let FormPurpose::ChangeMasterPassword = purpose {
+            let iterations = match form.value(3).trim().parse::<u32>() {
+                Ok(i) if (MIN_ITERATIONS..=MAX_ITERATIONS).contains(&i) => i,
+                _ => {
+                    form.set_error(&format!(
+                        "Iterations must be a number from {} to {}",
+                        MIN_ITERATIONS, MAX_ITERATIONS
+                    ));
+                    return;
+                }
+            };
+            if form.value(1).is_empty() {
+                form.set_error("The master password must not be empty");
+            } else if form.value(1) != form.value(2) {
+                form.set_error("The new passwords do not match");
+            } else {
+                // the form stays open until the model accepted the current password
+                self.pending_change = Some(Change::Rekey {
+                    current: Secret::text(form.value(0).to_string()),
+                    new: Secret::text(form.value(1).to_string()),
+                    iterations,
+                });
+            }
+            return;
+        }
+        if let FormPurpose::NewGroup = purpose {
+            let name = form.value(0).trim();
+            if name.is_empty() {
+                form.set_error("A name is required");
+                return;
+            }
+            if self.groups.contains(name) {
+                form.set_error("The group already exists");
+                return;
+            }
+            self.pending_change = Some(Change::AddGroup(name.to_string()));
+            self.form = None;
+            return;
+        }
+
+        if form.value(0).trim().is_empty() {
+            form.set_error("A title is required");
+            return;
+        }
+        let mut record = match purpose {
+            FormPurpose::Edit(entry) => entry.take(),
+            _ => DbRecord::default(),
+        };
+        set_text(&mut record, RecordField::Title(form.value(0).to_string()));
+        set_text(
+            &mut record,
+            RecordField::Username(form.value(1).to_string()),
+        );
+        set_text(
+            &mut record,
+            RecordField::Password(form.value(PASSWORD_FIELD).to_string()),
+        );
+        set_text(&mut record, RecordField::Url(form.value(3).to_string()));
+        record.set_notes(form.value(4));
+        self.pending_change = match purpose {
+            FormPurpose::Edit(_) => Some(Change::Update(record)),
+            _ if form.value(5).trim().is_empty() => {
+                form.set_error("A group is required");
+                return;
+            }
+            _ => {
+                set_text(
+                    &mut record,
+                    RecordField::Group(form.value(5).trim().to_string()),
+                );
+                Some(Change::Add(record))
+            }
+        };
+        self.form = None;
+    }
+
+    fn reload_groups(&mut self, model: &SafeModel) {
+        self.groups.reload(model);
+    }
+
+    /// Select a group and optionally one of its entries
+    fn show_entry(&mut self, model: &SafeModel, group: Option<String>, entry: Option<Uuid>) {
+        let group = group.unwrap_or_default();
+        self.groups.select(&group);
+        self.active_group_name = group;
+        self.reload_entries(model, entry);
+        self.select_group = self.entries.state.selected().is_none();
+    }
+
+    /// Show the record after it changed, or the current group when it is gone
+    fn select_record(&mut self, model: &SafeModel, uuid: Option<Uuid>) {
+        let active = self.active_entry.as_ref().and_then(|e| e.expose().uuid());
+        let record = uuid.or(active).and_then(|u| model.by_uuid(u));
+        match record {
+            Some(record) => {
+                self.show_entry(model, record.group().map(str::to_string), record.uuid());
+                self.active_entry = Some(Secret::copy_of(record));
+            }
+            None => {
+                self.active_entry = None;
+                self.show_entry(model, Some(self.active_group_name.clone()), None);
+            }
+        }
+    }
+
+    /// Entries of the active group and its subgroups, or the expiring entries by date
+    fn reload_entries(&mut self, model: &SafeModel, select: Option<Uuid>) {
+        let group = self.active_group_name.as_str();
+        if group == EXPIRING {
+            self.entries.items = model
+                .expiring(self.config.expiring_days)
+                .into_iter()
+                .map(|(e, expiry)| EntryItem {
+                    title: e.title().unwrap_or(UNTITLED).to_string(),
+                    subgroup: Some(format!(
+                        "{}  {}",
+                        format_date(expiry),
+                        e.group().unwrap_or_default()
+                    )),
+                    uuid: e.uuid(),
+                    expiry: Some(expiry),
+                })
+                .collect();
+            self.select_entry(select);
+            return;
+        }
+        let records = match group {
+            UNGROUPED => model.by_group_name(UNGROUPED),
+            _ => model.by_group_path(group),
+        };
+        self.entries.items = records
+            .iter()
+            .map(|&e| {
+                let subgroup = e
+                    .group()
+                    .filter(|&g| g != group)
+                    .map(|g| g[group.len() + 1..].to_string());
+                // written by other tools, listed so that they can be given a title
+                EntryItem {
+                    title: e.title().unwrap_or(UNTITLED).to_string(),
+                    subgroup,
+                    uuid: e.uuid(),
+                    expiry: e.expiry(),
+                }
+            })
+            .collect();
+        self.entries
+            .items
+            .sort_by(|a, b| (&a.title, &a.subgroup).cmp(&(&b.title, &b.subgroup)));
+        self.select_entry(select);
+    }
+
+    fn select_entry(&mut self, select: Option<Uuid>) {
+        if let Some(uuid) = select {
+            let pos = self
+                .entries
+                .items
+                .iter()
+                .position(|it| it.uuid == Some(uuid));
+            self.entries.state.select(pos);
+        }
+    }
+
+    /// Group of new and moved entries, none in the virtual group
+    fn real_group(&self) -> &str {
+        match self.active_group_name.as_str() {
+            EXPIRING => "",
+            group => group,
+        }
+    }
+}
+
+/// Expired entries are red, entries expiring before `soon` yellow
+fn entry_item(entry: &EntryItem, now: u32, soon: u64) -> ListItem<'_> {
+    let text = Style::default().fg(Color::White).bg(Color::Black);
+    let title = match entry.expiry {
+        Some(expiry) if expiry <= now => text.fg(Color::LightRed),
+        Some(expiry) if u64::from(expiry) <= soon => text.fg(Color::Yellow),
+        _ => text,
+    };
+    let mut spans = vec![Span::styled(entry.title.as_str(), title)];
+    if let Some(subgroup) = &entry.subgroup {
+        spans.push(Span::styled(
+            format!("  {}", subgroup),
+            text.fg(Color::Gray),
+        ));
+    }
+    ListItem::new(Spans::from(spans)).style(text)
+}
+
+/// Title and group of a search hit with the matched characters highlighted
+fn result_item(hit: &SearchHit) -> ListItem<'_> {
+    let text = Style::default().fg(Color::White).bg(Color::Black);
+    let matched = text.fg(Color::Yellow).add_modifier(Modifier::BOLD);
+    let mut spans = highlight(&hit.title, &hit.title_positions, text, matched);
+    if let Some(group) = &hit.group {
+        spans.push(Span::styled("  ", text));
+        spans.extend(highlight(
+            group,
+            &hit.group_positions,
+            text.fg(Color::Gray),
+            matched,
+        ));
+    }
+    ListItem::new(Spans::from(spans)).style(text)
+}
+
+fn highlight(value: &str, positions: &[usize], style: Style, matched: Style) -> Vec<Span<'static>> {
+    value
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            let style = if positions.contains(&i) {
+                matched
+            } else {
+                style
+            };
+            Span::styled(c.to_string(), style)
+        })
+        .collect()
+}
+
+fn edit_form(entry: &DbRecord, reveal: bool) -> Form {
+    Form::new("Edit entry")
+        .reveal(reveal)
+        .field("title", entry.title().unwrap_or_default())
+        .field("username", entry.username().unwrap_or_default())
+        .secret("password", entry.password().unwrap_or_default())
+        .field("url", entry.url().unwrap_or_default())
+        .multiline(
+            "notes",
+            Secret::text(entry.note_lines().collect::<Vec<_>>().join("\n")),
+        )
+}
+
+fn new_entry_form(group: &str) -> Form {
+    Form::new("New entry")
+        .field("title", "")
+        .field("username", "")
+        .secret("password", "")
+        .field("url", "")
+        .multiline("notes", Secret::input())
+        .field("group", group)
+}
+
+/// Own policy of the entry, and the name of a policy of the header it uses instead
+fn entry_policy(entry: &DbRecord) -> (PasswordPolicy, Option<String>) {
+    let mut policy = entry
+        .password_policy()
+        .and_then(PasswordPolicy::parse)
+        .unwrap_or_default();
+    if let Some(symbols) = entry.own_symbols().filter(|s| !s.is_empty()) {
+        policy.symbols = Some(symbols.to_string());
+    }
+    (policy, entry.password_policy_name().map(str::to_string))
+}
+
+fn generate_hint(keys: &KeyMap) -> Option<String> {
+    keys.text_key(Action::Generate)
+        .map(|key| format!("{} generate a password", key))
+}
+
+/// Empty text removes the field instead of storing an empty value
+fn set_text(record: &mut DbRecord, field: RecordField) {
+    match &field {
+        RecordField::Title(s)
+        | RecordField::Username(s)
+        | RecordField::Password(s)
+        | RecordField::Url(s)
+        | RecordField::Group(s)
+            if s.is_empty() =>
+        {
+            record.remove(field.kind())
+        }
+        _ => record.set(field),
+    }
+}
+
+/// The code with the time it is valid, the bar runs out with the time step
+fn totp_gauge(totp: &TotpCode, copy_only: bool) -> Gauge<'_> {
+    // the last seconds leave little time to type the code
+    let color = if totp.remaining <= 5 {
+        Color::Red
+    } else {
+        Color::Green
+    };
+    let code = match copy_only {
+        true => Cow::Owned("*".repeat(totp.code.expose().len())),
+        false => Cow::Borrowed(totp.code.expose().as_str()),
+    };
+    Gauge::default()
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("One-time code, {}s left", totp.remaining)),
+        )
+        .gauge_style(Style::default().fg(color).bg(Color::Black))
+        .ratio(totp.remaining as f64 / totp.time_step as f64)
+        .label(Span::styled(
+            code,
+            Style::default()
+                .fg(Color::White)
+                .add_modifier(Modifier::BOLD),
+        ))
+}
+
+fn help_text(keys: &KeyMap) -> String {
+    let parts: Vec<String> = HELP
+        .iter()
+        .filter_map(|(action, text)| keys.key(*action).map(|key| format!("{} {}", key, text)))
+        .collect();
+    format!("Press {}", parts.join(", "))
+}
+
+fn search_help(keys: &KeyMap) -> String {
+    let up = keys.text_key(Action::Up).unwrap_or_default();
+    let down = keys.text_key(Action::Down).unwrap_or_default();
+    format!(
+        "Type to search all entries, {}/{} select, <Enter> go to the entry, <Esc> clear search, \
+         then {}/{} next/previous match",
+        up,
+        down,
+        keys.key(Action::NextMatch).unwrap_or_default(),
+        keys.key(Action::PreviousMatch).unwrap_or_default(),
+    )
 }
@@ -1,82 +1,96 @@
 use crate::contracts::UiWidgetVm;
+use crate::keymap::{Action, KeyMap};
+use crate::model::secret::Secret;
+use crate::ui::centered_rect;
 use crate::SafeModel;
-use crossterm::event;
-use crossterm::event::{KeyCode, KeyEvent};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 use tui::backend::Backend;
-use tui::layout::{Constraint, Direction, Layout, Rect};
+use tui::layout::{Constraint, Layout, Rect};
+use tui::text::Text;
 use tui::widgets::{Block, Borders, Clear, Paragraph};
 use tui::Frame;
 use unicode_width::UnicodeWidthStr;
+use uuid::Uuid;
 
-const HELP_TEXT: &str = "Left-Ctrl to unhide";
+#[derive(Eq, PartialEq)]
+enum Mode {
+    Unlock,
+    Create,
+}
 
 pub struct PasswordWidget {
-    key_input: String,
+    mode: Mode,
+    // prompts are answered one after the other, the last one confirms the one before
+    prompts: Vec<&'static str>,
+    inputs: Vec<Secret<String>>,
+    key_input: Secret<String>,
     error: String,
     has_error: bool,
     enter_done: bool,
     hide_pw: bool,
     is_done: bool,
+    keys: KeyMap,
 }
 
 impl<B: Backend> UiWidgetVm<B> for PasswordWidget {
     fn capture_key(&mut self, key: KeyEvent) {
+        if self.keys.text_action(&key) == Some(Action::Reveal) {
+            self.hide_pw = !self.hide_pw;
+            return;
+        }
         match key.code {
-            KeyCode::Backspace => {
-                self.key_input.pop();
-            }
-            KeyCode::Char(c) => {
-                self.key_input.push(c);
-            }
-            KeyCode::Enter => {
-                self.enter_done = true;
-            }
-            _ => {
-                self.hide_pw = true;
-            }
-        };
-        match key {
-            event::KeyEvent {
-                code: _,
-                modifiers: event::KeyModifiers::CONTROL,
-                ..
-            } => {
-                self.hide_pw = false;
-            }
-            _ => {
-                self.hide_pw = true;
+            KeyCode::Backspace => self.key_input.pop(),
+            KeyCode::Char(c)
+                if !key
+                    .modifiers
+                    .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+            {
+                self.key_input.push(c)
             }
+            KeyCode::Enter => self.next_prompt(),
+            _ => {}
         };
     }
 
     fn draw(&mut self, f: &mut Frame<B>, rec: Rect) {
         f.render_widget(Clear, rec);
         let pw_display = if self.hide_pw {
-            self.key_input.clone().chars().map(|_| "*").collect()
+            Text::from(
+                self.key_input
+                    .expose()
+                    .chars()
+                    .map(|_| "*")
+                    .collect::<String>(),
+            )
         } else {
-            self.key_input.clone()
+            Text::from(self.key_input.expose().as_str())
         };
 
         let input = Paragraph::new(pw_display).block(
             Block::default()
-                .title("Enter Password")
+                .title(self.prompts[self.inputs.len()])
                 .borders(Borders::ALL),
         );
-        let popup = self.centered_rect(60, 40, rec);
+        let popup = centered_rect(60, 40, rec);
         let chunks = Layout::default()
             .constraints([Constraint::Percentage(70), Constraint::Percentage(30)])
             .split(popup);
         f.render_widget(input, chunks[0]);
 
+        let help = match self.keys.text_key(Action::Reveal) {
+            Some(key) if self.hide_pw => format!("{} to show the password", key),
+            Some(key) => format!("{} to hide the password", key),
+            None => String::new(),
+        };
         let helptext = Paragraph::new(if !self.has_error {
-            HELP_TEXT
+            help.as_str()
         } else {
             self.error.as_str()
         })
         .block(Block::default().title("Help").borders(Borders::ALL));
         f.render_widget(helptext, chunks[1]);
         f.set_cursor(
-            chunks[0].x + self.key_input.width() as u16 + 1,
+            chunks[0].x + self.key_input.expose().width() as u16 + 1,
             chunks[0].y + 1,
         );
     }
@@ -85,55 +99,101 @@ impl<B: Backend> UiWidgetVm<B> for PasswordWidget {
         if !self.enter_done {
             return;
         }
-        if let Err(e) = model.unlock(self.key_input.as_str()) {
+        self.enter_done = false;
+        let result = match self.mode {
+            Mode::Unlock => model
+                .unlock(self.key_input.expose())
+                .map_err(|e| format!("Error in unlock pwsafe: {}", e)),
+            Mode::Create => model
+                .initialize(self.key_input.expose())
+                .map_err(|e| format!("Error in creating pwsafe: {}", e)),
+        };
+        if let Err(e) = result {
             self.has_error = true;
-            self.error = format!("Error in unlock pwsafe: {}", e);
+            self.error = e;
         } else {
             self.is_done = true;
+            self.key_input.wipe();
+            self.inputs.clear();
         }
     }
 
     fn is_done(&self) -> bool {
         self.is_done
     }
+
+    fn has_popup(&self) -> bool {
+        false
+    }
+
+    fn takes_text(&self) -> bool {
+        true
+    }
+
+    fn location(&self) -> Option<(String, Option<Uuid>)> {
+        None
+    }
+
+    fn commands(&self) -> Vec<Action> {
+        vec![Action::Reveal]
+    }
+
+    fn run_command(&mut self, action: Action) {
+        if action == Action::Reveal {
+            self.hide_pw = !self.hide_pw;
+        }
+    }
 }
 
 impl PasswordWidget {
-    pub fn new() -> Self {
+    pub fn new(keys: KeyMap) -> Self {
+        Self::with_prompts(Mode::Unlock, vec!["Enter Password"], keys)
+    }
+
+    /// Ask twice for the master password of a new safe
+    pub fn create(keys: KeyMap) -> Self {
+        Self::with_prompts(
+            Mode::Create,
+            vec!["New master password", "Confirm master password"],
+            keys,
+        )
+    }
+
+    fn with_prompts(mode: Mode, prompts: Vec<&'static str>, keys: KeyMap) -> Self {
         PasswordWidget {
+            mode,
+            prompts,
+            inputs: Vec::new(),
             enter_done: false,
             has_error: false,
             error: String::new(),
             hide_pw: true,
-            key_input: String::new(),
+            key_input: Secret::input(),
             is_done: false,
+            keys,
         }
     }
 
-    /// helper function to create a centered rect using up certain percentage of the available rect `r`
-    fn centered_rect(&self, percent_x: u16, percent_y: u16, r: Rect) -> Rect {
-        let popup_layout = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints(
-                [
-                    Constraint::Percentage((100 - percent_y) / 2),
-                    Constraint::Percentage(percent_y),
-                    Constraint::Percentage((100 - percent_y) / 2),
-                ]
-                .as_ref(),
-            )
-            .split(r);
-
-        Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints(
-                [
-                    Constraint::Percentage((100 - percent_x) / 2),
-                    Constraint::Percentage(percent_x),
-                    Constraint::Percentage((100 - percent_x) / 2),
-                ]
-                .as_ref(),
-            )
-            .split(popup_layout[1])[1]
+    fn next_prompt(&mut self) {
+        if self.mode != Mode::Unlock && self.key_input.expose().is_empty() {
+            self.has_error = true;
+            self.error = "The master password must not be empty".to_string();
+            return;
+        }
+        if self.inputs.len() + 1 < self.prompts.len() {
+            let input = std::mem::replace(&mut self.key_input, Secret::input());
+            self.inputs.push(input);
+            self.has_error = false;
+            return;
+        }
+        let confirms = self.prompts.len() > 1;
+        if confirms && self.inputs.last().map(Secret::expose) != Some(self.key_input.expose()) {
+            self.has_error = true;
+            self.error = "The passwords do not match".to_string();
+            self.inputs.pop();
+            self.key_input.wipe();
+            return;
+        }
+        self.enter_done = true;
     }
 }
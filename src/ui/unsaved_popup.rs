@@ -0,0 +1,93 @@
+use crate::ui::centered_rect;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use tui::backend::Backend;
+use tui::layout::Rect;
+use tui::style::{Color, Style};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, Clear, Paragraph, Wrap};
+use tui::Frame;
+
+const HELP_TEXT: &str = "<y> save, <n> discard, <Esc> cancel";
+const LOCKED_HELP_TEXT: &str = "<n> discard, <Esc> cancel";
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Answer {
+    Save,
+    Discard,
+    Cancel,
+}
+
+/// Popup asking what happens to unsaved changes before the safe is closed
+pub struct UnsavedPopup {
+    message: String,
+    // changes of a locked safe can't be saved without the passphrase
+    can_save: bool,
+    answer: Option<Answer>,
+}
+
+impl UnsavedPopup {
+    /// The question names what follows, e.g. `quitting`
+    pub fn new(before: &str) -> Self {
+        UnsavedPopup {
+            message: format!("The safe has unsaved changes, save them before {}?", before),
+            can_save: true,
+            answer: None,
+        }
+    }
+
+    /// For the changes a locked safe keeps, they can only be discarded
+    pub fn locked(before: &str) -> Self {
+        UnsavedPopup {
+            message: format!(
+                "The changes made before the lock are not saved, unlock the safe to save them. \
+                 Discard them before {}?",
+                before
+            ),
+            can_save: false,
+            answer: None,
+        }
+    }
+
+    pub fn capture_key(&mut self, key: KeyEvent) {
+        if key
+            .modifiers
+            .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+        {
+            return;
+        }
+        self.answer = match key.code {
+            KeyCode::Char('y' | 'Y' | 's' | 'S') if self.can_save => Some(Answer::Save),
+            KeyCode::Char('n' | 'N' | 'd' | 'D') => Some(Answer::Discard),
+            KeyCode::Esc => Some(Answer::Cancel),
+            _ => None,
+        };
+    }
+
+    pub fn answer(&self) -> Option<Answer> {
+        self.answer
+    }
+
+    pub fn draw<B: Backend>(&self, f: &mut Frame<B>, rec: Rect) {
+        let popup = centered_rect(50, 30, rec);
+        let text = vec![
+            Spans::from(self.message.as_str()),
+            Spans::from(""),
+            Spans::from(Span::styled(
+                if self.can_save {
+                    HELP_TEXT
+                } else {
+                    LOCKED_HELP_TEXT
+                },
+                Style::default().fg(Color::Gray),
+            )),
+        ];
+        let question = Paragraph::new(text).wrap(Wrap { trim: true }).block(
+            Block::default()
+                .title("Unsaved changes")
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Yellow)),
+        );
+        f.render_widget(Clear, popup);
+        f.render_widget(question, popup);
+    }
+}
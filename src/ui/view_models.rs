@@ -1,66 +1,226 @@
-use crate::contracts::UiWidgetVm;
+use crate::config::Config;
+use crate::contracts::{TuiPwSafeErrors, UiWidgetVm};
+use crate::keymap::Action;
+use crate::ui::command_palette::CommandPalette;
+use crate::ui::error_popup::ErrorPopup;
 use crate::ui::list_content::ContentList;
 use crate::ui::password_input::PasswordWidget;
+use crate::ui::unsaved_popup::{Answer, UnsavedPopup};
 use crate::SafeModel;
+use crossterm::cursor::Show;
 use crossterm::event::Event::{Key, Mouse};
 use crossterm::event::{
-    DisableMouseCapture, EnableMouseCapture, KeyCode, KeyEvent, KeyModifiers, MouseButton,
-    MouseEventKind,
+    DisableMouseCapture, EnableMouseCapture, KeyCode, KeyEvent, MouseButton, MouseEventKind,
 };
 use crossterm::terminal::{
     disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
 };
 use crossterm::{event, execute};
-use std::error::Error;
-use std::io;
+#[cfg(unix)]
+use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
+use std::{io, panic};
 use tui::backend::{Backend, CrosstermBackend};
 use tui::{Frame, Terminal};
+use uuid::Uuid;
 
 #[derive(Eq, PartialEq)]
 enum View {
     PasswordDialog,
     ListStoreContent,
-    CurrentView,
+    Current,
 }
 
 struct App<B: Backend> {
     pub should_quit: bool,
     pub active_mv: Box<dyn UiWidgetVm<B>>,
+    // drawn on top of the active view and takes all keys while open
+    pub palette: Option<CommandPalette>,
+    // asks about unsaved changes, the action runs after the answer
+    pub unsaved: Option<(Action, UnsavedPopup)>,
+    // a failure of the app itself, e.g. saving before quitting
+    pub error: Option<ErrorPopup>,
     pub next_view: View,
     pub current_view: View,
+    pub config: Config,
+    pub last_input: Instant,
+    // where to continue after the idle lock
+    pub resume: Option<(String, Option<Uuid>)>,
+    // number of the termination signal received, 0 while running
+    pub signal: Arc<AtomicUsize>,
 }
 
 impl<B: Backend> App<B> {
-    pub fn new() -> Self {
+    pub fn new(config: Config, signal: Arc<AtomicUsize>) -> Self {
         App {
-            active_mv: Box::new(PasswordWidget::new()),
+            active_mv: Box::new(PasswordWidget::new(config.keys.clone())),
+            palette: None,
+            unsaved: None,
+            error: None,
             should_quit: false,
             next_view: View::PasswordDialog,
             current_view: View::PasswordDialog,
+            config,
+            last_input: Instant::now(),
+            resume: None,
+            signal,
+        }
+    }
+
+    /// Lock the safe once it was idle for the configured time
+    fn lock_when_idle(&mut self, model: &mut SafeModel) {
+        let timeout = self.config.lock_timeout;
+        if !timeout.is_zero() && self.last_input.elapsed() >= timeout {
+            self.last_input = Instant::now();
+            // nobody is there to answer, unsaved changes are kept for the next unlock
+            self.lock(model, true);
+        }
+    }
+
+    /// Lock the safe and ask for the passphrase, the location is restored after unlocking
+    fn lock(&mut self, model: &mut SafeModel, keep_changes: bool) {
+        if !model.is_unlocked() {
+            return;
+        }
+        self.resume = self.active_mv.location();
+        model.lock(keep_changes);
+        self.palette = None;
+        self.unsaved = None;
+        self.next_view = View::PasswordDialog;
+    }
+
+    fn handle_key(&mut self, key: KeyEvent, model: &mut SafeModel) {
+        if self.error.is_some() {
+            if matches!(key.code, KeyCode::Enter | KeyCode::Esc) {
+                self.error = None;
+            }
+            return;
+        }
+        if let Some((action, popup)) = &mut self.unsaved {
+            popup.capture_key(key);
+            if let Some(answer) = popup.answer() {
+                let action = *action;
+                self.unsaved = None;
+                self.answer_unsaved(action, answer, model);
+            }
+            return;
+        }
+        if let Some(palette) = &mut self.palette {
+            palette.capture_key(key);
+            if palette.is_closed() {
+                let chosen = palette.chosen();
+                self.palette = None;
+                if let Some(action) = chosen {
+                    self.run_command(action, model);
+                }
+            }
+            return;
+        }
+        let action = if self.active_mv.takes_text() {
+            self.config.keys.text_action(&key)
+        } else {
+            self.config.keys.action(&key)
+        };
+        match action {
+            Some(action @ (Action::Quit | Action::Lock | Action::CommandPalette))
+                if !self.active_mv.has_popup() =>
+            {
+                self.run_command(action, model)
+            }
+            _ => self.active_mv.capture_key(key),
+        }
+    }
+
+    /// Run an action of the app or of the active view, from a key or the command palette
+    fn run_command(&mut self, action: Action, model: &mut SafeModel) {
+        match action {
+            Action::Quit if model.is_dirty() => {
+                self.unsaved = Some((action, UnsavedPopup::new("quitting")))
+            }
+            Action::Quit if model.has_locked_changes() => {
+                self.unsaved = Some((action, UnsavedPopup::locked("quitting")))
+            }
+            Action::Quit => self.should_quit = true,
+            Action::Lock if model.is_dirty() => {
+                self.unsaved = Some((action, UnsavedPopup::new("locking")))
+            }
+            Action::Lock => self.lock(model, false),
+            Action::CommandPalette => {
+                let mut commands = vec![Action::Quit];
+                if model.is_unlocked() {
+                    commands.push(Action::Lock);
+                }
+                commands.extend(self.active_mv.commands());
+                self.palette = Some(CommandPalette::new(commands, self.config.keys.clone()));
+            }
+            action => self.active_mv.run_command(action),
+        }
+    }
+
+    /// Save or drop the unsaved changes before quitting or locking. A failed save cancels
+    /// quitting, the lock happens anyway and keeps the changes for the next unlock
+    fn answer_unsaved(&mut self, action: Action, answer: Answer, model: &mut SafeModel) {
+        let saved = match answer {
+            Answer::Cancel => return,
+            Answer::Save => match model.save() {
+                Ok(_) => true,
+                Err(e) => {
+                    self.error = Some(ErrorPopup::new(format!("Saving failed: {}", e)));
+                    false
+                }
+            },
+            Answer::Discard => true,
+        };
+        match action {
+            Action::Quit if saved => self.should_quit = true,
+            Action::Lock => self.lock(model, !saved),
+            _ => {}
         }
     }
 }
 
-pub fn run(store: SafeModel) -> Result<(), Box<dyn Error>> {
+/// Run the terminal ui, the terminal is restored whether it ends normally or not.
+/// The model and the views are dropped before returning, which clears the clipboard
+/// and wipes their secrets, also on a termination signal or while unwinding a panic
+pub fn run(store: SafeModel, config: Config) -> Result<(), TuiPwSafeErrors> {
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        // the message would be lost on the alternate screen
+        restore_terminal();
+        hook(info);
+    }));
+    let signal = watch_signals();
     enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut term = Terminal::new(backend)?;
-    let app = App::new();
-    match run_app(&mut term, app, store) {
-        Ok(_) => (),
-        Err(e) => panic!("Error to run App: {:?}", e),
-    };
-    disable_raw_mode()?;
-    execute!(
-        term.backend_mut(),
+    let result = execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture)
+        .and_then(|_| Terminal::new(CrosstermBackend::new(io::stdout())))
+        .and_then(|mut term| run_app(&mut term, App::new(config, Arc::clone(&signal)), store));
+    restore_terminal();
+    match signal.load(Ordering::Relaxed) {
+        0 => Ok(result?),
+        signal => Err(TuiPwSafeErrors::Terminated(signal as i32)),
+    }
+}
+
+/// Termination signals end the loop instead of the process, so that everything is cleaned up
+fn watch_signals() -> Arc<AtomicUsize> {
+    let received = Arc::new(AtomicUsize::new(0));
+    #[cfg(unix)]
+    for signal in [SIGINT, SIGTERM, SIGHUP] {
+        let _ = signal_hook::flag::register_usize(signal, Arc::clone(&received), signal as usize);
+    }
+    received
+}
+
+fn restore_terminal() {
+    let _ = disable_raw_mode();
+    let _ = execute!(
+        io::stdout(),
         LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    term.show_cursor()?;
-    Ok(())
+        DisableMouseCapture,
+        Show
+    );
 }
 
 fn run_app<B: Backend>(
@@ -79,20 +239,11 @@ fn run_app<B: Backend>(
             .unwrap_or_else(|| Duration::from_secs(0));
         if event::poll(timeout)? {
             if let Ok(ev) = event::read() {
+                app.last_input = Instant::now();
                 match ev {
-                    Key(key) => match key {
-                        KeyEvent {
-                            code: KeyCode::Esc,
-                            kind: _,
-                            state: _,
-                            modifiers: KeyModifiers::NONE,
-                        } => app.should_quit = true,
-                        _ => app.active_mv.capture_key(key),
-                    },
-                    Mouse(mouse) => {
-                        if mouse.kind == MouseEventKind::Down(MouseButton::Left) {
-                            // print!("{:?}", mouse)
-                        }
+                    Key(key) => app.handle_key(key, &mut model),
+                    Mouse(mouse) if mouse.kind == MouseEventKind::Down(MouseButton::Left) => {
+                        // print!("{:?}", mouse)
                     }
                     _ => {}
                 }
@@ -101,10 +252,12 @@ fn run_app<B: Backend>(
 
         if last_tick.elapsed() >= tick_rate {
             app.active_mv.update_model(&mut model);
+            model.expire_clipboard();
+            app.lock_when_idle(&mut model);
             last_tick = Instant::now();
         }
 
-        if app.should_quit {
+        if app.should_quit || app.signal.load(Ordering::Relaxed) != 0 {
             return Ok(());
         }
     }
@@ -113,7 +266,7 @@ fn run_app<B: Backend>(
 fn next_view(cur: &View) -> View {
     match cur {
         View::PasswordDialog => View::ListStoreContent,
-        _ => View::CurrentView,
+        _ => View::Current,
     }
 }
 
@@ -126,15 +279,33 @@ fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App<B>, model: &mut SafeModel) {
 
     match app.next_view {
         View::PasswordDialog => {
-            app.active_mv = Box::new(PasswordWidget::new());
-            app.next_view = View::CurrentView;
+            let keys = app.config.keys.clone();
+            app.active_mv = Box::new(if model.is_new() {
+                PasswordWidget::create(keys)
+            } else {
+                PasswordWidget::new(keys)
+            });
+            app.next_view = View::Current;
         }
         View::ListStoreContent => {
-            app.active_mv = Box::new(ContentList::new(model));
-            app.next_view = View::CurrentView;
+            let mut list = ContentList::new(model, app.config.clone());
+            if let Some((group, entry)) = app.resume.take() {
+                list.restore(model, group, entry);
+            }
+            app.active_mv = Box::new(list);
+            app.next_view = View::Current;
         }
         _ => {}
     };
 
     app.active_mv.draw(f, size);
+    if let Some(palette) = &mut app.palette {
+        palette.draw(f, size);
+    }
+    if let Some((_, popup)) = &app.unsaved {
+        popup.draw(f, size);
+    }
+    if let Some(error) = &app.error {
+        error.draw(f, size);
+    }
 }